@@ -8,7 +8,7 @@ use either::Either;
 use serde::{Deserialize, Serialize};
 use tonlib_core::TonAddress;
 use tower_http::cors::CorsLayer;
-use viewer::{Viewer, ViewerError, ViewerResult};
+use viewer::{Viewer, ViewerError, ViewerResult, indexer::Indexer};
 
 #[derive(Deserialize)]
 struct Env {
@@ -19,6 +19,8 @@ struct Env {
     viewer_api_key: Option<String>,
     collection_address: String,
     store_address: String,
+    /// Without an indexer, items are only listed through the viewer.
+    indexer_url: Option<String>,
 }
 
 fn capture_error(err: &anyhow::Error) {
@@ -45,6 +47,8 @@ async fn main() {
 
     let viewer = Viewer::new(env.viewer_api_url, env.viewer_api_key);
 
+    let indexer = env.indexer_url.map(Indexer::new);
+
     let (collection_address, _, _) =
         TonAddress::from_base64_url_flags(&env.collection_address).unwrap();
 
@@ -71,6 +75,15 @@ async fn main() {
                         return (StatusCode::BAD_REQUEST, "Invalid address").into_response();
                     };
 
+                    // The indexer answers without going through the viewer
+                    // queue, which is only used while the indexer is down.
+                    if let Some(indexer) = &indexer {
+                        match indexer.get_items(&owner_address, params.page).await {
+                            Ok(items) => return Json(items).into_response(),
+                            Err(err) => capture_error(&err),
+                        }
+                    }
+
                     let result = viewer
                         .get_items(collection_address, owner_address, Some(params.page))
                         .await;
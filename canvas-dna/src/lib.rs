@@ -0,0 +1,577 @@
+#![forbid(unused_must_use)]
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::must_use_candidate,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc
+)]
+
+//! DNA of Canvas items: the 64×64 grid of 6-bit palette codes, in the cell
+//! layout the collection contract validates and hashes.
+
+use base64::{
+    Engine, alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+use bitvec::{BitArr, array::BitArray, field::BitField, order::Lsb0, vec::BitVec, view::BitView};
+use flate2::{Compression, Crc, read::DeflateDecoder, write::DeflateEncoder};
+use itertools::Itertools;
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{Read, Write},
+};
+use tonlib_core::{
+    cell::{Cell, CellBuilder, TonCellError},
+    tlb_types::tlb::TLB,
+};
+
+/// Upper bound on the length of `Dna::to_link`, reached when the bits do
+/// not compress: a 3 byte header, 3072 bytes of bits and a 4 byte checksum.
+pub const LINK_MAX_LENGTH: usize = ((3 + 3072 + 4) * 4_usize).div_ceil(3);
+
+/// Unpadded base64url that also reads links cut in the middle of a base64
+/// quantum, up to the last full byte.
+const LINK_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_allow_trailing_bits(true)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const LINK_VERSION: u8 = 1;
+const LINK_RAW: u8 = 0;
+const LINK_DEFLATED: u8 = 1;
+
+#[derive(Debug)]
+pub struct Dna(BitArr!(for 24_576, in u8));
+
+impl Dna {
+    pub fn from_base64(base64: &str) -> Option<Self> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .ok()?;
+
+        let bytes = <[u8; 3072]>::try_from(bytes).ok()?;
+
+        let bitarr = BitArray::new(bytes);
+
+        Some(Self(bitarr))
+    }
+
+    pub fn to_base64(&self) -> String {
+        let bytes = self.0.as_raw_slice();
+
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// URL-safe form for sharing links: a header with the format version,
+    /// whether the bits are deflated and the payload length, then the
+    /// payload and a CRC-32 of the raw bits, all in unpadded base64url. Never
+    /// longer than `LINK_MAX_LENGTH`.
+    pub fn to_link(&self) -> String {
+        let raw = self.0.as_raw_slice();
+
+        let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+        encoder.write_all(raw).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let (mode, payload) = if deflated.len() < raw.len() {
+            (LINK_DEFLATED, deflated.as_slice())
+        } else {
+            (LINK_RAW, raw)
+        };
+
+        let mut crc = Crc::new();
+        crc.update(raw);
+
+        let mut bytes = vec![LINK_VERSION << 4 | mode];
+        bytes.extend(u16::try_from(payload.len()).unwrap().to_be_bytes());
+        bytes.extend(payload);
+        bytes.extend(crc.sum().to_be_bytes());
+
+        LINK_ENGINE.encode(bytes)
+    }
+
+    /// Tells links cut short, as happens when they are pasted or forwarded,
+    /// apart from ones that are otherwise damaged. Cut links are only
+    /// detected, the missing artwork can't be recovered from them.
+    pub fn from_link(link: &str) -> Result<Self, DnaLinkError> {
+        let link = link.trim();
+
+        // Links are base64url, and other characters would not fall on the
+        // byte boundaries the cut below relies on.
+        if !link.is_ascii() {
+            return Err(DnaLinkError::Invalid);
+        }
+
+        // A single character past the last full byte carries no data.
+        let link = &link[..link.len() - usize::from(link.len() % 4 == 1)];
+
+        let bytes = LINK_ENGINE
+            .decode(link)
+            .map_err(|_| DnaLinkError::Invalid)?;
+
+        let Some((&[header, length_high, length_low], rest)) = bytes.split_first_chunk() else {
+            return Err(DnaLinkError::Truncated);
+        };
+
+        if header >> 4 != LINK_VERSION {
+            return Err(DnaLinkError::Invalid);
+        }
+
+        let length = usize::from(u16::from_be_bytes([length_high, length_low]));
+
+        if rest.len() < length + 4 {
+            return Err(DnaLinkError::Truncated);
+        }
+        if rest.len() > length + 4 {
+            return Err(DnaLinkError::Invalid);
+        }
+
+        let (payload, checksum) = rest.split_at(length);
+
+        let raw = match header & 0xf {
+            LINK_RAW => payload.to_vec(),
+            LINK_DEFLATED => {
+                let mut raw = vec![];
+                DeflateDecoder::new(payload)
+                    .take(3073)
+                    .read_to_end(&mut raw)
+                    .map_err(|_| DnaLinkError::Invalid)?;
+                raw
+            }
+            _ => return Err(DnaLinkError::Invalid),
+        };
+
+        let raw = <[u8; 3072]>::try_from(raw).map_err(|_| DnaLinkError::Invalid)?;
+
+        let mut crc = Crc::new();
+        crc.update(&raw);
+        if crc.sum().to_be_bytes() != checksum {
+            return Err(DnaLinkError::Invalid);
+        }
+
+        Ok(Self(BitArray::new(raw)))
+    }
+
+    /// Takes rows of palette codes, keeping the low 6 bits of each.
+    pub fn from_data(data: &[Vec<u8>]) -> Self {
+        let mut bitvec = BitVec::new();
+        for row in data {
+            for pixel in row {
+                let bits = pixel.view_bits::<Lsb0>();
+                bitvec.extend_from_bitslice(&bits[..6]);
+            }
+        }
+
+        let mut bitarr = BitArray::new([0; _]);
+        bitarr.copy_from_bitslice(&bitvec);
+        Self(bitarr)
+    }
+
+    /// Palette codes row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = u8> {
+        self.0.chunks(6).map(BitField::load::<u8>)
+    }
+
+    pub fn to_data(&self) -> Vec<Vec<u8>> {
+        self.0
+            .chunks(6)
+            .map(BitField::load::<u8>)
+            .chunks(64)
+            .into_iter()
+            .map(Itertools::collect_vec)
+            .collect_vec()
+    }
+
+    /// Representation hash of the DNA cell, same as the `dna_hash` getter of
+    /// the item contract.
+    pub fn hash(&self) -> [u8; 32] {
+        self.to_cell().cell_hash().into()
+    }
+
+    /// Low 224 bits of the hash, used by `idFromDna` in `collection.tact` as
+    /// the id of `SuccessfulMinting` and `FailedMinting` messages.
+    pub fn mint_id(&self) -> [u8; 28] {
+        let hash = self.hash();
+        hash[4..].try_into().unwrap()
+    }
+
+    pub fn from_boc(boc_b64: &str) -> Result<Self, DnaCellError> {
+        let cell = Cell::from_boc_b64(boc_b64).map_err(|_| DnaCellError::InvalidBoc)?;
+
+        Self::from_cell(&cell)
+    }
+
+    pub fn from_cell(cell: &Cell) -> Result<Self, DnaCellError> {
+        validate_dna_cell(cell)?;
+
+        let mut bits = BitVec::<u8, Lsb0>::new();
+
+        let mut put_bits = |cell: &Cell| {
+            let mut parser = cell.parser();
+            let n = cell.bit_len();
+
+            let full_bytes = n / 8;
+            let leftover_bits = n % 8;
+            let full_bits = full_bytes * 8;
+
+            if full_bits > 0 {
+                let raw = parser.load_bits(full_bits)?;
+                bits.extend_from_bitslice(&raw.view_bits::<Lsb0>()[..full_bits]);
+            }
+
+            for _ in 0..leftover_bits {
+                bits.push(parser.load_bit()?);
+            }
+
+            Ok::<_, TonCellError>(())
+        };
+
+        let mut stack = vec![cell];
+        while let Some(cell) = stack.pop() {
+            put_bits(cell).map_err(|_| DnaCellError::InvalidBoc)?;
+            stack.extend(cell.references().iter().rev().map(AsRef::as_ref));
+        }
+
+        let mut bitarr = BitArray::new([0; _]);
+        bitarr.copy_from_bitslice(&bits);
+
+        Ok(Self(bitarr))
+    }
+
+    pub fn to_cell(&self) -> Cell {
+        let mut offset = 0;
+
+        let mut put_bits = |builder: &mut CellBuilder, n: usize| {
+            if n == 0 {
+                return;
+            }
+
+            let slice = &self.0[offset..offset + n];
+            offset += n;
+
+            let full_bytes = n / 8;
+            let leftover_bits = n % 8;
+            let full_bits = full_bytes * 8;
+
+            if full_bits > 0 {
+                let full_slice = &slice[..full_bits];
+                let mut bits = full_slice.to_bitvec();
+                bits.force_align();
+                bits.set_uninitialized(false);
+                let vec = bits.into_vec();
+                builder.store_bits(full_bits, &vec).unwrap();
+            }
+
+            for i in 0..leftover_bits {
+                builder.store_bit(slice[full_bits + i]).unwrap();
+            }
+        };
+
+        let put_ref = |builder: &mut CellBuilder, cell: Cell| {
+            builder.store_reference(&cell.to_arc()).unwrap();
+        };
+
+        let mut level0 = CellBuilder::new();
+
+        put_bits(&mut level0, 1023);
+
+        let mut is_leftmost_branch = true;
+
+        for _ in 0..4 {
+            let mut level1 = CellBuilder::new();
+            put_bits(&mut level1, 1023);
+
+            for _ in 0..4 {
+                let mut level2 = CellBuilder::new();
+                put_bits(&mut level2, 1023);
+
+                if is_leftmost_branch {
+                    for _ in 0..3 {
+                        let mut level3 = CellBuilder::new();
+                        put_bits(&mut level3, 1023);
+                        put_ref(&mut level2, level3.build().unwrap());
+                    }
+
+                    let mut level3_last = CellBuilder::new();
+                    put_bits(&mut level3_last, 24);
+                    put_ref(&mut level2, level3_last.build().unwrap());
+
+                    is_leftmost_branch = false;
+                }
+
+                put_ref(&mut level1, level2.build().unwrap());
+            }
+
+            put_ref(&mut level0, level1.build().unwrap());
+        }
+
+        level0.build().unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub enum DnaLinkError {
+    Truncated,
+    Invalid,
+}
+
+impl Display for DnaLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "DNA link is truncated"),
+            Self::Invalid => write!(f, "DNA link is invalid"),
+        }
+    }
+}
+
+impl Error for DnaLinkError {}
+
+#[derive(Debug)]
+pub enum DnaCellError {
+    InvalidBoc,
+    WrongBits {
+        path: Vec<usize>,
+        expected: usize,
+        actual: usize,
+    },
+    WrongRefs {
+        path: Vec<usize>,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl Display for DnaCellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidBoc => write!(f, "Invalid DNA BoC"),
+            Self::WrongBits {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "DNA cell at {path:?} has {actual} bits, expected {expected}"
+            ),
+            Self::WrongRefs {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "DNA cell at {path:?} has {actual} refs, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl Error for DnaCellError {}
+
+/// Checks the cell tree against the same shape rules as `validateDna` in
+/// `collection.tact`: full 1023-bit cells, 4 refs on the first two levels,
+/// and a single 4-leaf branch on the leftmost level-2 cell ending in a
+/// 24-bit leaf.
+pub fn validate_dna_cell(root: &Cell) -> Result<(), DnaCellError> {
+    let check = |cell: &Cell, path: &[usize], bits: usize, refs: usize| {
+        if cell.bit_len() != bits {
+            return Err(DnaCellError::WrongBits {
+                path: path.to_vec(),
+                expected: bits,
+                actual: cell.bit_len(),
+            });
+        }
+
+        if cell.references().len() != refs {
+            return Err(DnaCellError::WrongRefs {
+                path: path.to_vec(),
+                expected: refs,
+                actual: cell.references().len(),
+            });
+        }
+
+        Ok(())
+    };
+
+    check(root, &[], 1023, 4)?;
+
+    let mut is_leftmost_branch = true;
+
+    for (i, level1) in root.references().iter().enumerate() {
+        check(level1, &[i], 1023, 4)?;
+
+        for (j, level2) in level1.references().iter().enumerate() {
+            let refs = if is_leftmost_branch { 4 } else { 0 };
+            check(level2, &[i, j], 1023, refs)?;
+
+            for (k, level3) in level2.references().iter().enumerate() {
+                let bits = if k == 3 { 24 } else { 1023 };
+                check(level3, &[i, j, k], bits, 0)?;
+            }
+
+            is_leftmost_branch = false;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonlib_core::cell::ArcCell;
+
+    fn stripes() -> Dna {
+        let data = (0..64)
+            .map(|y| (0..64).map(|x| (x / 8 + y) % 64).collect())
+            .collect::<Vec<_>>();
+        Dna::from_data(&data)
+    }
+
+    /// Doesn't deflate, so links take the raw form.
+    fn noise() -> Dna {
+        let mut state = 1_u32;
+        let data = (0..64)
+            .map(|_| {
+                (0..64)
+                    .map(|_| {
+                        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                        u8::try_from(state >> 26).unwrap()
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        Dna::from_data(&data)
+    }
+
+    #[test]
+    fn link_round_trip() {
+        for dna in [stripes(), noise()] {
+            let link = dna.to_link();
+            assert!(link.len() <= LINK_MAX_LENGTH);
+            assert_eq!(Dna::from_link(&link).unwrap().0, dna.0);
+        }
+    }
+
+    #[test]
+    fn cut_links_are_truncated() {
+        for dna in [stripes(), noise()] {
+            let link = dna.to_link();
+            for length in 0..link.len() {
+                assert!(
+                    matches!(
+                        Dna::from_link(&link[..length]),
+                        Err(DnaLinkError::Truncated)
+                    ),
+                    "cut at {length}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_links_are_invalid() {
+        let mut link = stripes().to_link().into_bytes();
+        let middle = link.len() / 2;
+        link[middle] = if link[middle] == b'A' { b'B' } else { b'A' };
+        let link = String::from_utf8(link).unwrap();
+
+        assert!(matches!(Dna::from_link(&link), Err(DnaLinkError::Invalid)));
+        assert!(matches!(Dna::from_link("aaaé"), Err(DnaLinkError::Invalid)));
+    }
+
+    /// A cell of zero bits, as only the shape of the tree is validated.
+    fn blank_cell(bits: usize, refs: &[ArcCell]) -> ArcCell {
+        let mut builder = CellBuilder::new();
+        for _ in 0..bits {
+            builder.store_bit(false).unwrap();
+        }
+        builder.store_references(refs).unwrap();
+        builder.build().unwrap().to_arc()
+    }
+
+    /// Rebuilds the tree with the cell at `path` replaced by `replace` called
+    /// with its bit length and refs.
+    fn edit(
+        cell: &Cell,
+        path: &[usize],
+        replace: &dyn Fn(usize, Vec<ArcCell>) -> ArcCell,
+    ) -> ArcCell {
+        let mut refs = cell.references().to_vec();
+
+        match path.split_first() {
+            None => replace(cell.bit_len(), refs),
+            Some((&index, rest)) => {
+                refs[index] = edit(&refs[index], rest, replace);
+                blank_cell(cell.bit_len(), &refs)
+            }
+        }
+    }
+
+    #[test]
+    fn cell_round_trip() {
+        for dna in [stripes(), noise()] {
+            assert_eq!(Dna::from_cell(&dna.to_cell()).unwrap().0, dna.0);
+        }
+    }
+
+    #[test]
+    fn cells_of_wrong_shape_are_rejected() {
+        let root = stripes().to_cell();
+
+        // One path per level, with the leftmost branch going a level deeper
+        // and ending in the short leaf.
+        for path in [&[][..], &[2], &[0, 0], &[1, 3], &[0, 0, 1], &[0, 0, 3]] {
+            let wrong_bits = edit(&root, path, &|bits, refs| blank_cell(bits - 1, &refs));
+            assert!(
+                matches!(
+                    Dna::from_cell(&wrong_bits),
+                    Err(DnaCellError::WrongBits { path: actual, .. }) if actual == path,
+                ),
+                "wrong bits at {path:?}",
+            );
+
+            let wrong_refs = edit(&root, path, &|bits, mut refs| {
+                if refs.pop().is_none() {
+                    refs.push(blank_cell(0, &[]));
+                }
+                blank_cell(bits, &refs)
+            });
+            assert!(
+                matches!(
+                    Dna::from_cell(&wrong_refs),
+                    Err(DnaCellError::WrongRefs { path: actual, .. }) if actual == path,
+                ),
+                "wrong refs at {path:?}",
+            );
+        }
+    }
+
+    /// Hashes of the uniform DNAs that `makeDna` builds in the contract
+    /// tests, with every bit cleared and set. They were computed apart from
+    /// this crate, by hashing the cell tree of `makeDna` the way TON defines
+    /// representation hashes, as there are no minted items to take them from.
+    #[test]
+    fn hashes_match_the_contract() {
+        let fixtures = [
+            (
+                0,
+                "e131a1b7fba6d2e7e81df3bbe4a385588b9b99e28afb68fce6f5f4fd94439a77",
+            ),
+            (
+                63,
+                "d15cb8331a202f82b2f519ad86098d2482498e9251d8cdef2ec57c38b02e1ac3",
+            ),
+        ];
+
+        for (code, dna_hash) in fixtures {
+            let dna = Dna::from_data(&vec![vec![code; 64]; 64]);
+            assert_eq!(hex::encode(dna.hash()), dna_hash);
+            // `idFromDna` keeps the low 224 bits.
+            assert_eq!(hex::encode(dna.mint_id()), dna_hash[8..]);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+#![warn(clippy::pedantic, clippy::todo)]
+#![forbid(unused_must_use)]
+use crate::store::Store;
+use axum::{
+    Json, Router, extract,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing,
+};
+use canvas_dna::Dna;
+use either::Either;
+use serde::Deserialize;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tonlib_core::TonAddress;
+use tower_http::cors::CorsLayer;
+use viewer::{
+    NftItemsResponse, Viewer, ViewerError, ViewerResult,
+    indexer::{DnaLookup, IndexedItem, parse_dna_hash},
+    item_address::item_address,
+};
+
+mod store;
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How many indexed items get their owner and resale value fetched again per
+/// sync, as transfers and resales don't change the next item index. The whole
+/// collection is gone through in turns, so stored owners can lag behind
+/// transfers by a full round.
+const REFRESH_BATCH_SIZE: u64 = 10;
+
+#[derive(Deserialize)]
+struct Env {
+    port: u16,
+    app_version: Option<String>,
+    sentry_dsn: Option<String>,
+    viewer_api_url: String,
+    viewer_api_key: Option<String>,
+    collection_address: String,
+    database_path: String,
+}
+
+#[derive(Deserialize)]
+struct ItemsParams {
+    owner_address: String,
+    page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct DnaLookupParams {
+    hash: String,
+}
+
+fn capture_error(err: &anyhow::Error) {
+    sentry::integrations::anyhow::capture_anyhow(err);
+    eprintln!("{err}");
+}
+
+#[tokio::main]
+async fn main() {
+    let env = envy::from_env::<Env>().unwrap();
+
+    let mut sentry = None;
+
+    if let Some(sentry_dsn) = env.sentry_dsn {
+        sentry = Some(sentry::init((
+            sentry_dsn,
+            sentry::ClientOptions {
+                send_default_pii: true,
+                release: env.app_version.map(Into::into),
+                ..Default::default()
+            },
+        )));
+    }
+
+    let store = Store::open(&env.database_path).unwrap();
+
+    let viewer = Viewer::new(env.viewer_api_url, env.viewer_api_key);
+
+    // Set once every minted item was indexed, so that lookup misses are final.
+    let is_complete = Arc::new(AtomicBool::new(false));
+
+    tokio::spawn(run(
+        store.clone(),
+        viewer,
+        env.collection_address,
+        is_complete.clone(),
+    ));
+
+    let cors = CorsLayer::permissive();
+
+    let app = Router::new()
+        .route(
+            "/items/{item_index}",
+            routing::get({
+                let store = store.clone();
+                async move |item_index: extract::Path<u64>| {
+                    handle_store_result(store.get_item(item_index.0), |item| {
+                        item.map_or_else(
+                            || (StatusCode::NOT_FOUND, "Not Found").into_response(),
+                            |item| Json(item).into_response(),
+                        )
+                    })
+                }
+            }),
+        )
+        .route(
+            "/items",
+            routing::get({
+                let store = store.clone();
+                let is_complete = is_complete.clone();
+                async move |params: extract::Query<ItemsParams>| {
+                    // Callers fall back to the viewer until every item is
+                    // indexed, as a partial listing would look final.
+                    if !is_complete.load(Ordering::Relaxed) {
+                        return (StatusCode::SERVICE_UNAVAILABLE, "Indexing").into_response();
+                    }
+
+                    let Ok((owner_address, _, _)) =
+                        TonAddress::from_base64_url_flags(&params.owner_address)
+                    else {
+                        return (StatusCode::BAD_REQUEST, "Invalid address").into_response();
+                    };
+
+                    handle_store_result(
+                        store.get_items_of(&owner_address, params.page.unwrap_or(0)),
+                        |(items, has_next_page)| {
+                            Json(NftItemsResponse {
+                                items: items.iter().map(IndexedItem::to_nft_item).collect(),
+                                has_next_page,
+                            })
+                            .into_response()
+                        },
+                    )
+                }
+            }),
+        )
+        .route(
+            "/api/dna/lookup",
+            routing::get(async move |params: extract::Query<DnaLookupParams>| {
+                let Some(hash) = parse_dna_hash(&params.hash) else {
+                    return (StatusCode::BAD_REQUEST, "Invalid hash").into_response();
+                };
+
+                handle_store_result(store.find_by_dna_hash(&hash), |item_index| {
+                    Json(DnaLookup {
+                        exists: item_index.is_some(),
+                        item_index,
+                        is_complete: is_complete.load(Ordering::Relaxed),
+                    })
+                    .into_response()
+                })
+            }),
+        )
+        .route("/health", routing::get(async || "ok"))
+        .layer(cors);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", env.port))
+        .await
+        .unwrap();
+
+    println!("Indexer is started, listening to requests...");
+
+    axum::serve(listener, app).await.unwrap();
+
+    drop(sentry);
+}
+
+async fn run(
+    store: Store,
+    viewer: Viewer,
+    collection_address: String,
+    is_complete: Arc<AtomicBool>,
+) {
+    let mut refresh_cursor = 0;
+
+    loop {
+        let mut result = sync(&store, &viewer, &collection_address).await;
+
+        if result.is_ok() {
+            is_complete.store(true, Ordering::Relaxed);
+            result = refresh(&store, &viewer, &collection_address, &mut refresh_cursor).await;
+        }
+
+        match result {
+            Ok(()) => {}
+            Err(Either::Left(ViewerError::OverCapacity)) => {
+                eprintln!("Too many requests");
+            }
+            Err(Either::Right(err)) => capture_error(&err),
+        }
+
+        tokio::time::sleep(SYNC_INTERVAL).await;
+    }
+}
+
+async fn sync(store: &Store, viewer: &Viewer, collection_address: &str) -> ViewerResult<()> {
+    let (collection, _, _) = TonAddress::from_base64_url_flags(collection_address)
+        .map_err(|err| Either::Right(anyhow::Error::new(err)))?;
+
+    let next_item_index = viewer.get_next_item_index(collection).await?;
+
+    for item_index in store.next_item_index().map_err(Either::Right)?..next_item_index {
+        let item_address = item_address(collection_address, item_index);
+
+        let dna = viewer.get_dna(item_address.clone()).await?;
+        let data = viewer.get_nft_data(item_address).await?;
+
+        let dna_hash = Dna::from_boc(&dna)
+            .map_err(|err| {
+                Either::Right(
+                    anyhow::Error::new(err).context(format!("invalid DNA of item {item_index}")),
+                )
+            })?
+            .hash();
+
+        let item = IndexedItem {
+            dna,
+            dna_hash: hex::encode(dna_hash),
+            data,
+        };
+
+        store
+            .put_item(item_index, &dna_hash, &item)
+            .map_err(Either::Right)?;
+
+        println!("Indexed item {item_index}");
+    }
+
+    Ok(())
+}
+
+/// Fetches the data of the next batch of indexed items again, to follow
+/// transfers and resales. `cursor` is where the batch starts, and wraps around
+/// to the first item at the end of the collection.
+async fn refresh(
+    store: &Store,
+    viewer: &Viewer,
+    collection_address: &str,
+    cursor: &mut u64,
+) -> ViewerResult<()> {
+    let next_item_index = store.next_item_index().map_err(Either::Right)?;
+    if *cursor >= next_item_index {
+        *cursor = 0;
+    }
+
+    let end = cursor
+        .saturating_add(REFRESH_BATCH_SIZE)
+        .min(next_item_index);
+
+    for item_index in *cursor..end {
+        let data = viewer
+            .get_nft_data(item_address(collection_address, item_index))
+            .await?;
+
+        if store.put_data(item_index, data).map_err(Either::Right)? {
+            println!("Refreshed item {item_index}");
+        }
+
+        // Advanced per item, so a failed fetch is retried next time.
+        *cursor = item_index + 1;
+    }
+
+    Ok(())
+}
+
+fn handle_store_result<T>(result: anyhow::Result<T>, f: impl FnOnce(T) -> Response) -> Response {
+    match result {
+        Ok(data) => f(data),
+        Err(err) => {
+            capture_error(&err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use tonlib_core::TonAddress;
+use viewer::{NftData, PAGE_SIZE, indexer::IndexedItem};
+
+#[derive(Clone)]
+pub struct Store {
+    items: sled::Tree,
+    dna_hashes: sled::Tree,
+    /// Keys of the owner address followed by the item index, for listing the
+    /// items of an owner in order.
+    owners: sled::Tree,
+}
+
+fn owner_prefix(owner_address: &TonAddress) -> Vec<u8> {
+    let mut key = owner_address.to_hex().into_bytes();
+    key.push(b'/');
+    key
+}
+
+fn owner_key(owner_address: &TonAddress, item_index: u64) -> Vec<u8> {
+    let mut key = owner_prefix(owner_address);
+    key.extend(item_index.to_be_bytes());
+    key
+}
+
+impl Store {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let db = sled::open(path)?;
+
+        let store = Self {
+            items: db.open_tree("items")?,
+            dna_hashes: db.open_tree("dna_hashes")?,
+            owners: db.open_tree("owners")?,
+        };
+
+        // Databases from before the owner index get it built once.
+        if store.owners.is_empty() {
+            for entry in &store.items {
+                let (key, value) = entry?;
+                let item = serde_json::from_slice::<IndexedItem>(&value)?;
+                let item_index = u64::from_be_bytes(key.as_ref().try_into()?);
+                store
+                    .owners
+                    .insert(owner_key(&item.data.owner_address, item_index), &[])?;
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// Items are indexed in order, so the first missing index is one past the
+    /// last stored key.
+    pub fn next_item_index(&self) -> anyhow::Result<u64> {
+        let Some((key, _)) = self.items.last()? else {
+            return Ok(0);
+        };
+
+        Ok(u64::from_be_bytes(key.as_ref().try_into()?) + 1)
+    }
+
+    pub fn get_item(&self, item_index: u64) -> anyhow::Result<Option<IndexedItem>> {
+        let Some(value) = self.items.get(item_index.to_be_bytes())? else {
+            return Ok(None);
+        };
+
+        Ok(Some(serde_json::from_slice(&value)?))
+    }
+
+    /// A page of the items an address owns by index, and whether there is a
+    /// next page. Owners are only as fresh as the last refresh of each item,
+    /// so recent transfers may not show yet.
+    pub fn get_items_of(
+        &self,
+        owner_address: &TonAddress,
+        page: usize,
+    ) -> anyhow::Result<(Vec<IndexedItem>, bool)> {
+        let Some(skip) = page.checked_mul(PAGE_SIZE) else {
+            return Ok((vec![], false));
+        };
+
+        let prefix = owner_prefix(owner_address);
+        let mut items = vec![];
+
+        for entry in self
+            .owners
+            .scan_prefix(&prefix)
+            .skip(skip)
+            .take(PAGE_SIZE + 1)
+        {
+            let (key, _) = entry?;
+            let item_index = u64::from_be_bytes(key[prefix.len()..].try_into()?);
+            if let Some(item) = self.get_item(item_index)? {
+                items.push(item);
+            }
+        }
+
+        let has_next_page = items.len() > PAGE_SIZE;
+        items.truncate(PAGE_SIZE);
+
+        Ok((items, has_next_page))
+    }
+
+    pub fn find_by_dna_hash(&self, dna_hash: &[u8; 32]) -> anyhow::Result<Option<u64>> {
+        let Some(value) = self.dna_hashes.get(dna_hash)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(u64::from_be_bytes(value.as_ref().try_into()?)))
+    }
+
+    pub fn put_item(
+        &self,
+        item_index: u64,
+        dna_hash: &[u8; 32],
+        item: &IndexedItem,
+    ) -> anyhow::Result<()> {
+        // The hash goes in first: the items tree defines where indexing
+        // resumes, so a crash in between only repeats an idempotent write.
+        self.dna_hashes
+            .compare_and_swap(dna_hash, None::<&[u8]>, Some(&item_index.to_be_bytes()))?
+            .ok();
+        self.owners
+            .insert(owner_key(&item.data.owner_address, item_index), &[])?;
+        self.items
+            .insert(item_index.to_be_bytes(), serde_json::to_vec(item)?)?;
+
+        Ok(())
+    }
+
+    /// Replaces the data of an indexed item after a transfer or resale.
+    /// Returns whether anything changed.
+    pub fn put_data(&self, item_index: u64, data: NftData) -> anyhow::Result<bool> {
+        let Some(mut item) = self.get_item(item_index)? else {
+            return Ok(false);
+        };
+
+        let previous = serde_json::to_vec(&item)?;
+        let previous_owner = item.data.owner_address.clone();
+        item.data = data;
+        let next = serde_json::to_vec(&item)?;
+
+        if previous == next {
+            return Ok(false);
+        }
+
+        if previous_owner != item.data.owner_address {
+            self.owners.remove(owner_key(&previous_owner, item_index))?;
+            self.owners
+                .insert(owner_key(&item.data.owner_address, item_index), &[])?;
+        }
+        self.items.insert(item_index.to_be_bytes(), next)?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,299 @@
+#![forbid(unused_must_use)]
+#![warn(clippy::pedantic)]
+
+//! Looping GIF, APNG and WebP animations of 64×64 grids of palette codes.
+
+use image::{ExtendedColorType, ImageEncoder, Rgb, codecs::webp::WebPEncoder};
+use serde::Deserialize;
+
+/// Bounds of frame delays in milliseconds. Browsers slow down GIF frames
+/// shorter than 20 ms.
+pub const MIN_DELAY: u16 = 20;
+pub const MAX_DELAY: u16 = 10_000;
+
+/// Most frames a single animation can have.
+pub const MAX_FRAMES: usize = 32;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    Apng,
+    WebP,
+}
+
+impl AnimationFormat {
+    #[must_use]
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Gif => "image/gif",
+            Self::Apng => "image/apng",
+            Self::WebP => "image/webp",
+        }
+    }
+}
+
+/// One 64×64 grid of palette codes shown for `delay` milliseconds.
+pub struct Frame {
+    pub codes: Vec<u8>,
+    pub delay: u16,
+}
+
+/// Encodes frames into a looping animation, upscaling every pixel to a
+/// `scale`×`scale` block. Codes are looked up in `colors`, which must have
+/// all 64, and delays are clamped to `MIN_DELAY..=MAX_DELAY`. Returns `None`
+/// without any frames or with more than `MAX_FRAMES`.
+///
+/// # Panics
+///
+/// Panics if a frame is not 64×64 or has codes missing from `colors`.
+#[must_use]
+pub fn encode(
+    frames: &[Frame],
+    scale: usize,
+    format: AnimationFormat,
+    colors: &phf::Map<u8, Rgb<u8>>,
+) -> Option<Vec<u8>> {
+    if !(1..=MAX_FRAMES).contains(&frames.len()) {
+        return None;
+    }
+
+    assert!(frames.iter().all(|frame| frame.codes.len() == 64 * 64));
+
+    let side = 64 * scale;
+    let palette = palette(colors);
+    let frames = frames
+        .iter()
+        .map(|frame| {
+            (
+                upscale(&frame.codes, scale),
+                frame.delay.clamp(MIN_DELAY, MAX_DELAY),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Some(match format {
+        AnimationFormat::Gif => encode_gif(&frames, side, &palette),
+        AnimationFormat::Apng => encode_apng(&frames, side, palette),
+        AnimationFormat::WebP => encode_webp(&frames, side, &palette),
+    })
+}
+
+fn upscale(codes: &[u8], scale: usize) -> Vec<u8> {
+    codes
+        .chunks(64)
+        .flat_map(|row| {
+            let row = row
+                .iter()
+                .flat_map(|code| std::iter::repeat_n(*code, scale))
+                .collect::<Vec<_>>();
+            std::iter::repeat_n(row, scale).flatten()
+        })
+        .collect()
+}
+
+/// The palette as RGB triples ordered by code, for indexed formats.
+fn palette(colors: &phf::Map<u8, Rgb<u8>>) -> Vec<u8> {
+    (0..64)
+        .flat_map(|code| colors.get(&code).unwrap().0)
+        .collect()
+}
+
+fn encode_gif(frames: &[(Vec<u8>, u16)], side: usize, palette: &[u8]) -> Vec<u8> {
+    let side = u16::try_from(side).unwrap();
+    let mut bytes = vec![];
+
+    {
+        let mut encoder = gif::Encoder::new(&mut bytes, side, side, palette).unwrap();
+        encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+
+        for (indices, delay) in frames {
+            let mut frame = gif::Frame::from_indexed_pixels(side, side, indices.clone(), None);
+            // GIF delays are in hundredths of a second.
+            frame.delay = delay.div_ceil(10);
+            encoder.write_frame(&frame).unwrap();
+        }
+    }
+
+    bytes
+}
+
+fn encode_apng(frames: &[(Vec<u8>, u16)], side: usize, palette: Vec<u8>) -> Vec<u8> {
+    let side = u32::try_from(side).unwrap();
+    let mut bytes = vec![];
+
+    let mut encoder = png::Encoder::new(&mut bytes, side, side);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette);
+    encoder
+        .set_animated(u32::try_from(frames.len()).unwrap(), 0)
+        .unwrap();
+
+    let mut writer = encoder.write_header().unwrap();
+    for (indices, delay) in frames {
+        writer.set_frame_delay(*delay, 1000).unwrap();
+        writer.write_image_data(indices).unwrap();
+    }
+    writer.finish().unwrap();
+
+    bytes
+}
+
+/// `image` only writes still WebP images, so each frame is encoded on its
+/// own and its `VP8L` chunk wrapped into an animation container.
+fn encode_webp(frames: &[(Vec<u8>, u16)], side: usize, palette: &[u8]) -> Vec<u8> {
+    const ANIMATION_FLAG: u8 = 0x02;
+    const NO_BLENDING: u8 = 0x02;
+    const RIFF_HEADER_SIZE: usize = 12;
+
+    let side_minus_one = u24(side - 1);
+
+    let mut body = b"WEBP".to_vec();
+
+    let mut vp8x = vec![ANIMATION_FLAG, 0, 0, 0];
+    vp8x.extend(side_minus_one);
+    vp8x.extend(side_minus_one);
+    push_chunk(&mut body, *b"VP8X", &vp8x);
+
+    // Background colour and loop count, zero meaning forever.
+    push_chunk(&mut body, *b"ANIM", &[0, 0, 0, 0, 0, 0]);
+
+    for (indices, delay) in frames {
+        let rgb = indices
+            .iter()
+            .flat_map(|index| {
+                let index = usize::from(*index) * 3;
+                [palette[index], palette[index + 1], palette[index + 2]]
+            })
+            .collect::<Vec<_>>();
+
+        let mut still = vec![];
+        WebPEncoder::new_lossless(&mut still)
+            .write_image(
+                &rgb,
+                u32::try_from(side).unwrap(),
+                u32::try_from(side).unwrap(),
+                ExtendedColorType::Rgb8,
+            )
+            .unwrap();
+
+        let mut anmf = [0; 6].to_vec();
+        anmf.extend(side_minus_one);
+        anmf.extend(side_minus_one);
+        anmf.extend(u24(usize::from(*delay)));
+        anmf.push(NO_BLENDING);
+        anmf.extend(&still[RIFF_HEADER_SIZE..]);
+        push_chunk(&mut body, *b"ANMF", &anmf);
+    }
+
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend(u32::try_from(body.len()).unwrap().to_le_bytes());
+    bytes.extend(body);
+
+    bytes
+}
+
+fn u24(value: usize) -> [u8; 3] {
+    let [a, b, c, ..] = value.to_le_bytes();
+    [a, b, c]
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, fourcc: [u8; 4], data: &[u8]) {
+    bytes.extend(fourcc);
+    bytes.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+    bytes.extend(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{
+        AnimationDecoder,
+        codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    };
+    use std::io::Cursor;
+
+    static COLORS: phf::Map<u8, Rgb<u8>> = rust_colors::colors!(canvas);
+
+    const SCALE: usize = 2;
+
+    fn frames() -> Vec<Frame> {
+        let stripes = (0..64 * 64).map(|i| u8::try_from(i % 64).unwrap());
+        let checks = (0..64 * 64).map(|i| u8::try_from((i / 64 + i % 64) % 2 * 63).unwrap());
+
+        vec![
+            Frame {
+                codes: stripes.collect(),
+                delay: 100,
+            },
+            Frame {
+                codes: checks.collect(),
+                delay: 250,
+            },
+        ]
+    }
+
+    fn assert_decodes_to_frames(decoded: &[image::Frame]) {
+        let frames = frames();
+        assert_eq!(decoded.len(), frames.len());
+
+        for (decoded, frame) in decoded.iter().zip(&frames) {
+            assert_eq!(
+                decoded.delay().numer_denom_ms(),
+                (u32::from(frame.delay), 1)
+            );
+
+            let image = decoded.buffer();
+            let side = u32::try_from(64 * SCALE).unwrap();
+            assert_eq!(image.dimensions(), (side, side));
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let code = frame.codes[y as usize / SCALE * 64 + x as usize / SCALE];
+                let [r, g, b] = COLORS[&code].0;
+                assert_eq!(pixel.0, [r, g, b, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn gif_round_trip() {
+        let bytes = encode(&frames(), SCALE, AnimationFormat::Gif, &COLORS).unwrap();
+        let decoder = GifDecoder::new(Cursor::new(bytes)).unwrap();
+        assert_decodes_to_frames(&decoder.into_frames().collect_frames().unwrap());
+    }
+
+    #[test]
+    fn apng_round_trip() {
+        let bytes = encode(&frames(), SCALE, AnimationFormat::Apng, &COLORS).unwrap();
+        let decoder = PngDecoder::new(Cursor::new(bytes)).unwrap();
+        assert!(decoder.is_apng().unwrap());
+        let decoder = decoder.apng().unwrap();
+        assert_decodes_to_frames(&decoder.into_frames().collect_frames().unwrap());
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        let bytes = encode(&frames(), SCALE, AnimationFormat::WebP, &COLORS).unwrap();
+        let decoder = WebPDecoder::new(Cursor::new(bytes)).unwrap();
+        assert!(decoder.has_animation());
+        assert_decodes_to_frames(&decoder.into_frames().collect_frames().unwrap());
+    }
+
+    #[test]
+    fn frame_count_is_bounded() {
+        assert!(encode(&[], 1, AnimationFormat::Gif, &COLORS).is_none());
+
+        let frames = (0..=MAX_FRAMES)
+            .map(|_| Frame {
+                codes: vec![0; 64 * 64],
+                delay: 100,
+            })
+            .collect::<Vec<_>>();
+        assert!(encode(&frames[..MAX_FRAMES], 1, AnimationFormat::Gif, &COLORS).is_some());
+        assert!(encode(&frames, 1, AnimationFormat::Gif, &COLORS).is_none());
+    }
+}
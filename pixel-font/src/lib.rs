@@ -0,0 +1,298 @@
+#![forbid(unused_must_use)]
+#![warn(clippy::pedantic)]
+
+//! A 5×7 pixel font covering printable ASCII, drawn in palette colours at
+//! integer scales.
+
+use image::{Rgb, RgbImage};
+
+static COLORS: phf::Map<u8, Rgb<u8>> = rust_colors::colors!(canvas);
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// Distance between the left edges of consecutive glyphs, in font pixels.
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Distance between the tops of consecutive lines, in font pixels.
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Rows of the ASCII characters from space to tilde, five bits each with the
+/// leftmost pixel in the highest bit.
+const GLYPHS: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x04, 0x04, 0x04, 0x04, 0x00, 0x00, 0x04],
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00],
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04],
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E],
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00],
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E],
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E],
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F],
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E],
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08],
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11],
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E],
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C],
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12],
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11],
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11],
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E],
+    [0x00, 0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10],
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x01],
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10],
+    [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E],
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06],
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D],
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04],
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A],
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11],
+    [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E],
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F],
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02],
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08],
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00],
+];
+
+/// Characters missing from the font are drawn as a question mark.
+fn glyph(char: char) -> [u8; 7] {
+    let char = if matches!(char, ' '..='~') { char } else { '?' };
+
+    GLYPHS[(u32::from(char) - u32::from(' ')) as usize]
+}
+
+/// Size of `text` in image pixels, as its widest line by its number of
+/// lines. Spacing after the last glyph and below the last line is left out.
+/// Saturates instead of overflowing, so that callers can reject huge text.
+#[must_use]
+pub fn measure(text: &str, scale: u32) -> (u32, u32) {
+    let count = |count: usize| u32::try_from(count).unwrap_or(u32::MAX);
+    let columns = text
+        .lines()
+        .map(|line| count(line.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let lines = count(text.lines().count());
+
+    (
+        columns
+            .saturating_mul(ADVANCE)
+            .saturating_sub(1)
+            .saturating_mul(scale),
+        lines
+            .saturating_mul(LINE_HEIGHT)
+            .saturating_sub(LINE_HEIGHT - GLYPH_HEIGHT)
+            .saturating_mul(scale),
+    )
+}
+
+/// Draws text with its top left corner at `(x, y)` in the colour of a
+/// palette code, with every font pixel as a `scale`×`scale` block. Lines are
+/// split on line breaks and whatever falls outside the image is clipped.
+///
+/// # Panics
+///
+/// If `code` is not a palette code.
+pub fn draw_text(image: &mut RgbImage, x: u32, y: u32, text: &str, code: u8, scale: u32) {
+    let color = *COLORS.get(&code).expect("code is not in the palette");
+
+    for (line_index, line) in (0..).zip(text.lines()) {
+        // Lines and glyphs only move down and right, so drawing stops at the
+        // first one past the image.
+        let Some(top) =
+            offset(y, line_index, LINE_HEIGHT, scale).filter(|top| *top < image.height())
+        else {
+            break;
+        };
+
+        for (index, char) in (0..).zip(line.chars()) {
+            let Some(left) = offset(x, index, ADVANCE, scale).filter(|left| *left < image.width())
+            else {
+                break;
+            };
+
+            for (row, bits) in (0..GLYPH_HEIGHT).zip(glyph(char)) {
+                for column in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 0 {
+                        continue;
+                    }
+
+                    let xs = block(left, column, scale, image.width());
+                    for py in block(top, row, scale, image.height()) {
+                        for px in xs.clone() {
+                            image.put_pixel(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Image position of the `index`th glyph or line from `start`, `None` if it
+/// doesn't fit in a `u32`.
+fn offset(start: u32, index: u32, step: u32, scale: u32) -> Option<u32> {
+    index
+        .checked_mul(step)?
+        .checked_mul(scale)
+        .and_then(|offset| start.checked_add(offset))
+}
+
+/// Image positions covered by the `scale` pixels of the font pixel at
+/// `position` in a glyph starting at `start`, clipped to `end`.
+fn block(start: u32, position: u32, scale: u32, end: u32) -> std::ops::Range<u32> {
+    let from = start
+        .saturating_add(position.saturating_mul(scale))
+        .min(end);
+    from..from.saturating_add(scale).min(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+
+    /// Font pixels set in the image, as `#` on `.` rows.
+    fn pixels(image: &RgbImage) -> Vec<String> {
+        image
+            .rows()
+            .map(|row| {
+                row.map(|pixel| if *pixel == BLACK { '.' } else { '#' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn canvas(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_pixel(width, height, BLACK)
+    }
+
+    /// A code whose colour stands out from the black background.
+    fn lit_code() -> u8 {
+        *COLORS
+            .entries()
+            .find(|(_, color)| **color != BLACK)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn glyphs_are_placed_by_advance_and_line_height() {
+        let mut image = canvas(12, 16);
+        draw_text(&mut image, 0, 0, "|.\n-", lit_code(), 1);
+
+        let mut expected = vec!["..#.........".to_string(); 7];
+        expected[5] = "..#....##...".into();
+        expected[6] = "..#....##...".into();
+        expected.extend(vec!["............".to_string(); 6]);
+        expected[12] = "#####.......".into();
+
+        assert_eq!(pixels(&image)[..13], expected);
+        assert!(pixels(&image)[13..].iter().all(|row| !row.contains('#')));
+    }
+
+    #[test]
+    fn scaled_glyphs_are_clipped_at_the_edges() {
+        let mut image = canvas(6, 8);
+        draw_text(&mut image, 0, 0, "|", lit_code(), 2);
+        draw_text(&mut image, 0, 0, "-", lit_code(), 2);
+
+        let mut expected = vec!["....##"; 6];
+        expected.extend(["######"; 2]);
+        assert_eq!(pixels(&image), expected);
+    }
+
+    #[test]
+    fn text_far_outside_the_image_is_skipped() {
+        let mut image = canvas(8, 8);
+        draw_text(&mut image, u32::MAX - 1, 0, "ab", lit_code(), u32::MAX);
+        draw_text(&mut image, 0, u32::MAX, "a\nb", lit_code(), u32::MAX);
+        assert!(pixels(&image).iter().all(|row| !row.contains('#')));
+
+        draw_text(
+            &mut image,
+            0,
+            0,
+            &"E\n".repeat(100),
+            lit_code(),
+            u32::MAX / 2,
+        );
+        assert!(pixels(&image).iter().all(|row| row == "########"));
+    }
+
+    #[test]
+    fn unknown_characters_are_drawn_as_question_marks() {
+        let mut unknown = canvas(5, 7);
+        draw_text(&mut unknown, 0, 0, "€", lit_code(), 1);
+
+        let mut question_mark = canvas(5, 7);
+        draw_text(&mut question_mark, 0, 0, "?", lit_code(), 1);
+
+        assert_eq!(unknown, question_mark);
+        assert!(pixels(&unknown).iter().any(|row| row.contains('#')));
+    }
+}
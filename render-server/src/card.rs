@@ -0,0 +1,201 @@
+use crate::render::COLORS;
+use bytes::{BufMut, Bytes, BytesMut};
+use canvas_dna::Dna;
+use image::{RgbImage, codecs::png::PngEncoder};
+use itertools::Itertools;
+use viewer::NftData;
+
+/// Size Open Graph images are shown at.
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+
+const ARTWORK_SCALE: u32 = 8;
+const MARGIN: u32 = (HEIGHT - 64 * ARTWORK_SCALE) / 2;
+const TEXT_LEFT: u32 = 2 * MARGIN + 64 * ARTWORK_SCALE;
+const TEXT_WIDTH: u32 = WIDTH - TEXT_LEFT - MARGIN;
+
+const BACKGROUND: u8 = 1;
+const TEXT: u8 = 63;
+const MUTED: u8 = 5;
+const ACCENT: u8 = 41;
+
+const NANOTONS_PER_TON: u64 = 1_000_000_000;
+
+/// Renders the share card of an item: the artwork on the left, and its
+/// title, artist, artist fingerprint and last resale price on the right.
+pub async fn render(dna: Dna, data: NftData) -> Bytes {
+    tokio_rayon::spawn_fifo(move || {
+        let color = |code: u8| *COLORS.get(&code).unwrap();
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, color(BACKGROUND));
+
+        for (pixel, code) in (0..64).cartesian_product(0..64).zip(dna.pixels()) {
+            let (y, x) = pixel;
+            for (dy, dx) in (0..ARTWORK_SCALE).cartesian_product(0..ARTWORK_SCALE) {
+                image.put_pixel(
+                    MARGIN + x * ARTWORK_SCALE + dx,
+                    MARGIN + y * ARTWORK_SCALE + dy,
+                    color(code),
+                );
+            }
+        }
+
+        let mut line = |y: u32, text: &str, code: u8, scale: u32| {
+            pixel_font::draw_text(&mut image, TEXT_LEFT, y, &fit(text, scale), code, scale);
+        };
+
+        line(MARGIN, &format!("#{}", data.index), MUTED, 3);
+
+        if let Some(content) = &data.content {
+            line(
+                MARGIN + 60,
+                &drawable_title(&content.title, data.index),
+                TEXT,
+                5,
+            );
+            line(MARGIN + 120, &format!("by {}", content.artist), TEXT, 3);
+            line(
+                MARGIN + 160,
+                &abbreviate_fingerprint(&content.artist_fingerprint),
+                MUTED,
+                2,
+            );
+
+            if let Some(value) = content.last_resale_value {
+                line(
+                    HEIGHT - MARGIN - pixel_font::GLYPH_HEIGHT * 4,
+                    &format!("Last sale {}", format_ton(value)),
+                    ACCENT,
+                    4,
+                );
+            }
+        }
+
+        let mut bytes = BytesMut::new();
+        image
+            .write_with_encoder(PngEncoder::new((&mut bytes).writer()))
+            .unwrap();
+        bytes.freeze()
+    })
+    .await
+}
+
+/// HTML page that link previews are built from. `image_url` must be
+/// absolute, as crawlers don't resolve relative ones.
+pub fn page(data: &NftData, image_url: &str) -> String {
+    let (title, description) = match &data.content {
+        Some(content) => (
+            format!("{} #{}", title(&content.title), data.index),
+            format!("by {}", content.artist),
+        ),
+        None => (format!("#{}", data.index), String::new()),
+    };
+    let (title, description, image_url) = (
+        escape_html(&title),
+        escape_html(&description),
+        escape_html(image_url),
+    );
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<meta property="og:type" content="website">
+<meta property="og:title" content="{title}">
+<meta property="og:description" content="{description}">
+<meta property="og:image" content="{image_url}">
+<meta property="og:image:width" content="{WIDTH}">
+<meta property="og:image:height" content="{HEIGHT}">
+<meta name="twitter:card" content="summary_large_image">
+</head>
+<body>
+<img src="{image_url}" alt="{title}" width="{WIDTH}" height="{HEIGHT}">
+</body>
+</html>
+"#
+    )
+}
+
+fn title(title: &str) -> &str {
+    if title.trim().is_empty() {
+        "Untitled"
+    } else {
+        title
+    }
+}
+
+/// The title, or the item number when the pixel font has none of its
+/// characters, as with titles in other scripts.
+fn drawable_title(text: &str, index: u64) -> String {
+    let text = title(text);
+
+    if text.chars().any(|char| char.is_ascii_graphic()) {
+        text.into()
+    } else {
+        format!("#{index}")
+    }
+}
+
+/// Puts `text` on one line and cuts it with an ellipsis so that it fits
+/// next to the artwork.
+fn fit(text: &str, scale: u32) -> String {
+    let text = text.lines().collect::<Vec<_>>().join(" ");
+    let width = |text: &str| pixel_font::measure(text, scale).0;
+
+    if width(&text) <= TEXT_WIDTH {
+        return text;
+    }
+
+    let mut fitted = text;
+    while width(&format!("{fitted}...")) > TEXT_WIDTH {
+        fitted.pop();
+    }
+    format!("{}...", fitted.trim_end())
+}
+
+/// Shortens a fingerprint to its ends. The viewer drops leading zeros, which
+/// are put back first so that the start matches the full fingerprint.
+fn abbreviate_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = format!("{fingerprint:0>64}");
+
+    format!(
+        "{}...{}",
+        &fingerprint[..8],
+        &fingerprint[fingerprint.len() - 8..]
+    )
+}
+
+/// Formats nanotons as TON, without trailing zeros.
+fn format_ton(nanotons: u64) -> String {
+    let whole = nanotons / NANOTONS_PER_TON;
+    let fraction = nanotons % NANOTONS_PER_TON;
+
+    if fraction == 0 {
+        format!("{whole} TON")
+    } else {
+        let fraction = format!("{fraction:09}");
+        format!("{whole}.{} TON", fraction.trim_end_matches('0'))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_keep_leading_zeros() {
+        let fingerprint = format!("{:x}", 0x1234_5678_9abc_def0_u64);
+        assert_eq!(abbreviate_fingerprint(&fingerprint), "00000000...9abcdef0");
+
+        let fingerprint = "f".repeat(64);
+        assert_eq!(abbreviate_fingerprint(&fingerprint), "ffffffff...ffffffff");
+    }
+}
@@ -0,0 +1,92 @@
+use crate::render::COLORS;
+use bytes::{BufMut, Bytes, BytesMut};
+use canvas_dna::Dna;
+use image::{RgbImage, codecs::png::PngEncoder};
+use itertools::Itertools;
+
+/// Most items a single collage can have.
+pub const MAX_ITEMS: usize = 100;
+
+/// Most pixels a collage image can have, about 4096×4096.
+pub const MAX_PIXELS: u64 = 1 << 24;
+
+/// Most output pixels per artwork pixel.
+pub const MAX_SCALE: u32 = 10;
+
+#[derive(Clone, Copy)]
+pub struct Layout {
+    pub columns: u32,
+    /// Space between and around items in output pixels.
+    pub gutter: u32,
+    /// Palette code the gutters are filled with.
+    pub background: u8,
+    pub scale: u32,
+}
+
+impl Layout {
+    /// Size of the image for `count` items, laid out in rows of `columns`,
+    /// or `None` if the layout is invalid or the image has more than
+    /// `MAX_PIXELS`.
+    pub fn dimensions(self, count: usize) -> Option<(u32, u32)> {
+        if self.columns == 0
+            || !(1..=MAX_SCALE).contains(&self.scale)
+            || self.background >= 64
+            || count == 0
+        {
+            return None;
+        }
+
+        let columns = u64::from(self.columns);
+        let rows = u64::try_from(count).ok()?.div_ceil(columns);
+        let side = |cells: u64| {
+            cells
+                .checked_mul(64 * u64::from(self.scale))?
+                .checked_add(cells.checked_add(1)?.checked_mul(u64::from(self.gutter))?)
+        };
+
+        let (width, height) = (side(columns)?, side(rows)?);
+        if width.checked_mul(height)? > MAX_PIXELS {
+            return None;
+        }
+
+        Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+    }
+}
+
+/// Renders items into a grid, row by row in the given order. The layout must
+/// have dimensions for the number of items.
+pub async fn render(dnas: Vec<Dna>, layout: Layout) -> Bytes {
+    tokio_rayon::spawn_fifo(move || {
+        let (width, height) = layout.dimensions(dnas.len()).unwrap();
+        let background = *COLORS.get(&layout.background).unwrap();
+        let mut image = RgbImage::from_pixel(width, height, background);
+
+        let cell = 64 * layout.scale + layout.gutter;
+
+        for (index, dna) in dnas.iter().enumerate() {
+            let index = u32::try_from(index).unwrap();
+            let left = layout.gutter + index % layout.columns * cell;
+            let top = layout.gutter + index / layout.columns * cell;
+
+            for (pixel, code) in (0..64).cartesian_product(0..64).zip(dna.pixels()) {
+                let (y, x) = pixel;
+                let color = *COLORS.get(&code).unwrap();
+
+                for (dy, dx) in (0..layout.scale).cartesian_product(0..layout.scale) {
+                    image.put_pixel(
+                        left + x * layout.scale + dx,
+                        top + y * layout.scale + dy,
+                        color,
+                    );
+                }
+            }
+        }
+
+        let mut bytes = BytesMut::new();
+        image
+            .write_with_encoder(PngEncoder::new((&mut bytes).writer()))
+            .unwrap();
+        bytes.freeze()
+    })
+    .await
+}
@@ -1,24 +1,37 @@
 #![warn(clippy::pedantic, clippy::todo)]
 #![forbid(unused_must_use)]
-use crate::{dna::Dna, item_address::item_address, storage::Storage};
+use crate::{render::COLORS, storage::Storage};
 use axum::{
-    Router,
+    Json, Router,
     body::Body,
     extract,
-    http::{StatusCode, header},
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
     routing::{self},
 };
+use bytes::Bytes;
+use canvas_dna::Dna;
 use either::Either;
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
+use pixel_animation::{AnimationFormat, MAX_FRAMES};
 use serde::Deserialize;
+use std::str::FromStr;
+use tonlib_core::TonAddress;
 use tower_http::cors::CorsLayer;
-use viewer::{Viewer, ViewerError};
+use viewer::{
+    NftData, Viewer, ViewerError,
+    indexer::{DnaLookup, IndexedItem, Indexer, parse_dna_hash},
+    item_address::item_address,
+};
 
-mod dna;
-mod item_address;
+mod card;
+mod collage;
 mod render;
 mod storage;
+mod warmup;
+
+/// Most DNAs a single request fetches at once.
+const FETCH_CONCURRENCY: usize = 4;
 
 #[derive(Deserialize)]
 struct Env {
@@ -33,6 +46,41 @@ struct Env {
     viewer_api_url: String,
     viewer_api_key: Option<String>,
     collection_address: String,
+    /// Without an indexer, items are only fetched through the viewer and DNA
+    /// lookups are unavailable.
+    indexer_url: Option<String>,
+    warmup_rate: Option<u32>,
+    /// Base URL the server is reachable at, for absolute links in pages.
+    /// Falls back to the `Host` header.
+    public_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DnaLookupParams {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct CollageParams {
+    /// Comma separated item indices, in the order they are laid out.
+    items: Option<String>,
+    /// Address whose items are laid out instead, by index.
+    owner: Option<String>,
+    columns: Option<u32>,
+    gutter: Option<u32>,
+    background: Option<u8>,
+    scale: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct AnimParams {
+    /// Comma separated item indices, one per frame.
+    items: String,
+    /// Comma separated frame delays in milliseconds.
+    delay: Option<String>,
+    #[serde(default)]
+    format: AnimationFormat,
+    scale: Option<usize>,
 }
 
 fn capture_error(err: &anyhow::Error) {
@@ -40,6 +88,147 @@ fn capture_error(err: &anyhow::Error) {
     eprintln!("{err}");
 }
 
+fn viewer_error_response(err: Either<ViewerError, anyhow::Error>) -> Response {
+    match err {
+        Either::Left(ViewerError::OverCapacity) => {
+            eprintln!("Too many requests");
+            (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests").into_response()
+        }
+        Either::Right(err) => {
+            capture_error(&err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+        }
+    }
+}
+
+/// Looks an item up in the indexer, which is missing the latest items and
+/// may be unreachable, in which case the viewer is asked instead.
+async fn fetch_indexed(indexer: Option<&Indexer>, item_index: u64) -> Option<IndexedItem> {
+    indexer?.get_item(item_index).await.unwrap_or_else(|err| {
+        capture_error(&err.context(format!("indexed item {item_index}")));
+        None
+    })
+}
+
+/// Fetches and parses the DNA of an item, turning failures into the
+/// response to send back.
+async fn fetch_dna(
+    viewer: &Viewer,
+    indexer: Option<&Indexer>,
+    collection_address: &str,
+    item_index: u64,
+) -> Result<Dna, Response> {
+    let raw_dna = match fetch_indexed(indexer, item_index).await {
+        Some(item) => item.dna,
+        None => viewer
+            .get_dna(item_address(collection_address, item_index))
+            .await
+            .map_err(viewer_error_response)?,
+    };
+
+    Dna::from_boc(&raw_dna).map_err(|err| {
+        capture_error(&anyhow::Error::new(err).context(format!("item {item_index}")));
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+    })
+}
+
+/// Fetches the DNA of several items in order, a few at a time so that a
+/// single request doesn't fill the viewer queue.
+async fn fetch_dnas(
+    viewer: &Viewer,
+    indexer: Option<&Indexer>,
+    collection_address: &str,
+    item_indices: &[u64],
+) -> Result<Vec<Dna>, Response> {
+    futures::stream::iter(item_indices.iter().copied())
+        .map(|item_index| fetch_dna(viewer, indexer, collection_address, item_index))
+        .buffered(FETCH_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+async fn fetch_nft_data(
+    viewer: &Viewer,
+    indexer: Option<&Indexer>,
+    collection_address: &str,
+    item_index: u64,
+) -> Result<NftData, Response> {
+    if let Some(item) = fetch_indexed(indexer, item_index).await {
+        return Ok(item.data);
+    }
+
+    viewer
+        .get_nft_data(item_address(collection_address, item_index))
+        .await
+        .map_err(viewer_error_response)
+}
+
+/// Serves a DNA lookup from the indexer, which knows every minted item.
+async fn lookup_dna(indexer: Option<&Indexer>, hash: &[u8; 32]) -> Response {
+    let Some(indexer) = indexer else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable").into_response();
+    };
+
+    match indexer.lookup_dna(hash).await {
+        Ok(lookup) => Json::<DnaLookup>(lookup).into_response(),
+        Err(err) => {
+            capture_error(&err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error").into_response()
+        }
+    }
+}
+
+/// Serves a file rendered before, if storage has it. Storage failures are
+/// answered too, so that they don't turn into a rush of renders.
+async fn stored_file(
+    storage: Option<&Storage>,
+    path: &str,
+    content_type: &'static str,
+) -> Option<Response> {
+    let stream = match storage?.get(path).await {
+        Ok(stream) => stream?,
+        Err(err) => {
+            capture_error(&err);
+            return Some(
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Service Temporarily Unavailable",
+                )
+                    .into_response(),
+            );
+        }
+    };
+
+    Some(
+        (
+            [(header::CONTENT_TYPE, content_type)],
+            Body::from_stream(stream.map_err(|err| {
+                capture_error(&err);
+                anyhow::Error::msg("Internal Error")
+            })),
+        )
+            .into_response(),
+    )
+}
+
+/// Saves a rendered file in the background, for `stored_file` to serve.
+fn store_file(storage: Option<&Storage>, path: String, file: Bytes) {
+    if let Some(storage) = storage.cloned() {
+        tokio::spawn(async move {
+            if let Err(err) = storage.put(&path, file).await {
+                capture_error(&err);
+            }
+        });
+    }
+}
+
+/// Parses a comma separated list like `1,5,9`.
+fn parse_list<T: FromStr>(list: &str) -> Option<Vec<T>> {
+    list.split(',')
+        .map(|item| item.trim().parse().ok())
+        .collect()
+}
+
 #[allow(clippy::too_many_lines)]
 #[tokio::main]
 async fn main() {
@@ -72,85 +261,328 @@ async fn main() {
 
     let viewer = Viewer::new(env.viewer_api_url, env.viewer_api_key);
 
+    let mut args = std::env::args().skip(1);
+
+    if args.next().as_deref() == Some("warmup") {
+        let from = args.next().map_or(0, |from| from.parse().unwrap());
+        let to = args.next().map(|to| to.parse().unwrap());
+
+        let result = warmup::warmup(
+            &viewer,
+            storage.as_ref().expect("warmup requires S3 storage"),
+            &env.collection_address,
+            from,
+            to,
+            env.warmup_rate.unwrap_or(1),
+        )
+        .await;
+
+        if let Err(err) = result {
+            capture_error(&err);
+            std::process::exit(1);
+        }
+
+        println!("Warmup is finished");
+
+        return;
+    }
+
+    let indexer = env.indexer_url.map(Indexer::new);
+
     let cors = CorsLayer::permissive();
 
     let app = Router::new()
         .route(
             "/img/{item_index}",
             routing::get({
+                let storage = storage.clone();
+                let viewer = viewer.clone();
+                let indexer = indexer.clone();
+                let collection_address = env.collection_address.clone();
                 async move |item_index: extract::Path<u64>| {
                     let item_index = item_index.0;
-                    let item_address = item_address(&env.collection_address, item_index);
-                    let raw_dna = match viewer.get_dna(item_address).await {
-                        Ok(raw_dna) => raw_dna,
-                        Err(Either::Left(ViewerError::OverCapacity)) => {
-                            eprintln!("Too many requests");
-                            return (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests")
-                                .into_response();
-                        }
-                        Err(Either::Right(err)) => {
-                            capture_error(&err);
-                            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error")
-                                .into_response();
-                        }
+                    let path = format!("image/{item_index}");
+
+                    if let Some(response) = stored_file(storage.as_ref(), &path, "image/png").await
+                    {
+                        return response;
+                    }
+
+                    // Only a cache miss costs a viewer call.
+                    let dna =
+                        match fetch_dna(&viewer, indexer.as_ref(), &collection_address, item_index)
+                            .await
+                        {
+                            Ok(dna) => dna,
+                            Err(response) => return response,
+                        };
+
+                    let file = Box::pin(render::render(dna)).await;
+
+                    store_file(storage.as_ref(), path, file.clone());
+
+                    ([(header::CONTENT_TYPE, "image/png")], file).into_response()
+                }
+            }),
+        )
+        .route(
+            "/anim",
+            routing::get({
+                let viewer = viewer.clone();
+                let indexer = indexer.clone();
+                let collection_address = env.collection_address.clone();
+                async move |extract::Query::<AnimParams>(params)| {
+                    let Some(items) = parse_list::<u64>(&params.items)
+                        .filter(|items| (1..=MAX_FRAMES).contains(&items.len()))
+                    else {
+                        return (StatusCode::BAD_REQUEST, "Invalid items").into_response();
                     };
 
-                    let dna = match Dna::from_boc(&raw_dna) {
-                        Ok(parsed_dna) => parsed_dna,
-                        Err(err) => {
-                            capture_error(&err);
-                            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal Error")
-                                .into_response();
-                        }
+                    // Either one delay for every frame or one per frame.
+                    let delays = parse_list::<u16>(params.delay.as_deref().unwrap_or("200"))
+                        .filter(|delays| delays.len() == 1 || delays.len() == items.len());
+                    let Some(delays) = delays else {
+                        return (StatusCode::BAD_REQUEST, "Invalid delay").into_response();
                     };
 
-                    let path = format!("image/{item_index}");
+                    let scale = params.scale.unwrap_or(10);
+                    if !(1..=10).contains(&scale) {
+                        return (StatusCode::BAD_REQUEST, "Invalid scale").into_response();
+                    }
+
+                    let dnas =
+                        match fetch_dnas(&viewer, indexer.as_ref(), &collection_address, &items)
+                            .await
+                        {
+                            Ok(dnas) => dnas,
+                            Err(response) => return response,
+                        };
+
+                    let frames = dnas
+                        .iter()
+                        .zip(delays.iter().cycle())
+                        .map(|(dna, delay)| pixel_animation::Frame {
+                            codes: dna.pixels().collect(),
+                            delay: *delay,
+                        })
+                        .collect::<Vec<_>>();
+
+                    let format = params.format;
+                    let Some(file) = tokio_rayon::spawn_fifo(move || {
+                        pixel_animation::encode(&frames, scale, format, &COLORS)
+                    })
+                    .await
+                    else {
+                        return (StatusCode::BAD_REQUEST, "Invalid items").into_response();
+                    };
 
-                    let existing_file = if let Some(storage) = &storage {
-                        match storage.get(&path).await {
-                            Ok(existing_file) => existing_file,
-                            Err(err) => {
-                                capture_error(&err);
-                                return (
-                                    StatusCode::SERVICE_UNAVAILABLE,
-                                    "Service Temporarily Unavailable",
-                                )
+                    ([(header::CONTENT_TYPE, format.content_type())], file).into_response()
+                }
+            }),
+        )
+        .route(
+            "/collage",
+            routing::get({
+                let viewer = viewer.clone();
+                let indexer = indexer.clone();
+                let collection_address = env.collection_address.clone();
+                async move |extract::Query::<CollageParams>(params)| {
+                    let items = match (params.items, params.owner) {
+                        (Some(items), None) => {
+                            let Some(items) = parse_list::<u64>(&items) else {
+                                return (StatusCode::BAD_REQUEST, "Invalid items").into_response();
+                            };
+                            items
+                        }
+                        (None, Some(owner)) => {
+                            let Ok((owner, _, _)) = TonAddress::from_base64_url_flags(&owner)
+                            else {
+                                return (StatusCode::BAD_REQUEST, "Invalid address")
                                     .into_response();
+                            };
+                            let (collection, _, _) =
+                                TonAddress::from_base64_url_flags(&collection_address).unwrap();
+
+                            // One page more than fits is enough to reject the
+                            // owner as having too many items.
+                            let mut items = vec![];
+                            for page in 0.. {
+                                let response = match viewer
+                                    .get_items(collection.clone(), owner.clone(), Some(page))
+                                    .await
+                                {
+                                    Ok(response) => response,
+                                    Err(err) => return viewer_error_response(err),
+                                };
+                                items.extend(response.items.iter().map(|item| item.index));
+
+                                if !response.has_next_page || items.len() > collage::MAX_ITEMS {
+                                    break;
+                                }
                             }
+                            items.sort_unstable();
+                            items
+                        }
+                        _ => {
+                            return (StatusCode::BAD_REQUEST, "Expected either items or owner")
+                                .into_response();
                         }
-                    } else {
-                        None
                     };
 
-                    if let Some(stream) = existing_file {
-                        return (
-                            [(header::CONTENT_TYPE, "image/png")],
-                            Body::from_stream(stream.map_err(|err| {
-                                capture_error(&err);
-                                anyhow::Error::msg("Internal Error")
-                            })),
-                        )
+                    if !(1..=collage::MAX_ITEMS).contains(&items.len()) {
+                        return (StatusCode::BAD_REQUEST, "Invalid number of items")
                             .into_response();
                     }
 
-                    let file = Box::pin(render::render(dna)).await;
+                    // As square as possible by default.
+                    let count = u32::try_from(items.len()).unwrap();
+                    let columns = params.columns.unwrap_or_else(|| {
+                        (1..=count)
+                            .find(|columns| columns * columns >= count)
+                            .unwrap()
+                    });
 
-                    if let Some(storage) = storage {
-                        tokio::spawn({
-                            let file = file.clone();
-                            let storage = storage.clone();
-                            async move {
-                                if let Err(err) = storage.put(&path, file).await {
-                                    capture_error(&err);
-                                }
-                            }
-                        });
+                    let layout = collage::Layout {
+                        columns,
+                        gutter: params.gutter.unwrap_or(0),
+                        background: params.background.unwrap_or(0),
+                        scale: params.scale.unwrap_or(4),
+                    };
+
+                    if layout.dimensions(items.len()).is_none() {
+                        return (StatusCode::BAD_REQUEST, "Invalid layout").into_response();
+                    }
+
+                    let dnas =
+                        match fetch_dnas(&viewer, indexer.as_ref(), &collection_address, &items)
+                            .await
+                        {
+                            Ok(dnas) => dnas,
+                            Err(response) => return response,
+                        };
+
+                    let file = Box::pin(collage::render(dnas, layout)).await;
+
+                    ([(header::CONTENT_TYPE, "image/png")], file).into_response()
+                }
+            }),
+        )
+        .route(
+            "/card/{item_index}",
+            routing::get({
+                let storage = storage.clone();
+                let viewer = viewer.clone();
+                let indexer = indexer.clone();
+                let collection_address = env.collection_address.clone();
+                async move |item_index: extract::Path<u64>| {
+                    let item_index = item_index.0;
+                    let data = match fetch_nft_data(
+                        &viewer,
+                        indexer.as_ref(),
+                        &collection_address,
+                        item_index,
+                    )
+                    .await
+                    {
+                        Ok(data) => data,
+                        Err(response) => return response,
+                    };
+
+                    // Resales change the card, the rest of the content is
+                    // fixed at minting.
+                    let last_resale_value = data
+                        .content
+                        .as_ref()
+                        .and_then(|content| content.last_resale_value)
+                        .unwrap_or(0);
+                    let path = format!("card/{item_index}/{last_resale_value}");
+
+                    if let Some(response) = stored_file(storage.as_ref(), &path, "image/png").await
+                    {
+                        return response;
                     }
 
+                    let dna =
+                        match fetch_dna(&viewer, indexer.as_ref(), &collection_address, item_index)
+                            .await
+                        {
+                            Ok(dna) => dna,
+                            Err(response) => return response,
+                        };
+
+                    let file = Box::pin(card::render(dna, data)).await;
+
+                    store_file(storage.as_ref(), path, file.clone());
+
                     ([(header::CONTENT_TYPE, "image/png")], file).into_response()
                 }
             }),
         )
+        .route(
+            "/item/{item_index}",
+            routing::get({
+                let viewer = viewer.clone();
+                let indexer = indexer.clone();
+                let collection_address = env.collection_address.clone();
+                let public_url = env.public_url.clone();
+                async move |item_index: extract::Path<u64>, headers: HeaderMap| {
+                    let item_index = item_index.0;
+                    let data = match fetch_nft_data(
+                        &viewer,
+                        indexer.as_ref(),
+                        &collection_address,
+                        item_index,
+                    )
+                    .await
+                    {
+                        Ok(data) => data,
+                        Err(response) => return response,
+                    };
+
+                    let base_url = if let Some(public_url) = &public_url {
+                        public_url.trim_end_matches('/').to_owned()
+                    } else {
+                        let Some(host) = headers
+                            .get(header::HOST)
+                            .and_then(|host| host.to_str().ok())
+                        else {
+                            return (StatusCode::BAD_REQUEST, "Missing host").into_response();
+                        };
+                        format!("https://{host}")
+                    };
+
+                    let page = card::page(&data, &format!("{base_url}/card/{item_index}"));
+
+                    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], page).into_response()
+                }
+            }),
+        )
+        .route(
+            "/api/dna/lookup",
+            routing::get({
+                let indexer = indexer.clone();
+                async move |extract::Query::<DnaLookupParams>(params)| {
+                    let Some(hash) = parse_dna_hash(&params.hash) else {
+                        return (StatusCode::BAD_REQUEST, "Invalid hash").into_response();
+                    };
+
+                    lookup_dna(indexer.as_ref(), &hash).await
+                }
+            })
+            .post(async move |Json::<Vec<Vec<u8>>>(data)| {
+                if data.len() != 64
+                    || data
+                        .iter()
+                        .any(|row| row.len() != 64 || row.iter().any(|pixel| *pixel >= 64))
+                {
+                    return (StatusCode::BAD_REQUEST, "Invalid data").into_response();
+                }
+
+                let hash = Dna::from_data(&data).hash();
+
+                lookup_dna(indexer.as_ref(), &hash).await
+            }),
+        )
         .route("/health", routing::get(async || "ok"))
         .layer(cors);
 
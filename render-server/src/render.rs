@@ -1,10 +1,10 @@
-use crate::dna::Dna;
 use bytes::{BufMut, Bytes, BytesMut};
+use canvas_dna::Dna;
 use image::{RgbImage, codecs::png::PngEncoder};
 use itertools::Itertools;
 use std::iter;
 
-static COLORS: phf::Map<u8, image::Rgb<u8>> = rust_colors::colors!();
+pub static COLORS: phf::Map<u8, image::Rgb<u8>> = rust_colors::colors!(canvas);
 
 pub async fn render(dna: Dna) -> Bytes {
     tokio_rayon::spawn_fifo(move || {
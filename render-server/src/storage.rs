@@ -47,6 +47,17 @@ impl Storage {
         Ok(Some(result?.bytes.map_err(anyhow::Error::new)))
     }
 
+    pub async fn exists(&self, path: &str) -> anyhow::Result<bool> {
+        let result = self.0.head_object(path).await;
+
+        if let Err(s3::error::S3Error::HttpFailWithBody(404, _)) = result {
+            return Ok(false);
+        }
+
+        result?;
+        Ok(true)
+    }
+
     pub async fn put(&self, path: &str, payload: Bytes) -> anyhow::Result<()> {
         self.0.put_object(path, &payload).await?;
         Ok(())
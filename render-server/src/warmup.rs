@@ -0,0 +1,72 @@
+use crate::{render, storage::Storage};
+use canvas_dna::Dna;
+use either::Either;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tonlib_core::TonAddress;
+use viewer::{Viewer, ViewerError, item_address::item_address};
+
+const BACKOFF: Duration = Duration::from_secs(5);
+
+/// Renders and uploads every item in the range that is not in storage yet,
+/// at most `rate` items per second. Already uploaded images are skipped, so
+/// an interrupted run can be resumed by starting it again.
+pub async fn warmup(
+    viewer: &Viewer,
+    storage: &Storage,
+    collection_address: &str,
+    from: u64,
+    to: Option<u64>,
+    rate: u32,
+) -> anyhow::Result<()> {
+    let to = if let Some(to) = to {
+        to
+    } else {
+        let (collection, _, _) = TonAddress::from_base64_url_flags(collection_address)?;
+        retry_over_capacity(async || viewer.get_next_item_index(collection.clone()).await).await?
+    };
+
+    let total = to.saturating_sub(from);
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1) / rate.max(1));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    for (done, item_index) in (1..).zip(from..to) {
+        let path = format!("image/{item_index}");
+
+        if storage.exists(&path).await? {
+            println!("[{done}/{total}] Item {item_index} is already rendered");
+            continue;
+        }
+
+        interval.tick().await;
+
+        let item_address = item_address(collection_address, item_index);
+        let raw_dna = retry_over_capacity(async || viewer.get_dna(item_address.clone()).await)
+            .await
+            .map_err(|err| err.context(format!("failed to warm up item {item_index}")))?;
+
+        let dna = Dna::from_boc(&raw_dna)?;
+        let file = Box::pin(render::render(dna)).await;
+        storage.put(&path, file).await?;
+
+        println!("[{done}/{total}] Rendered item {item_index}");
+    }
+
+    Ok(())
+}
+
+async fn retry_over_capacity<T>(
+    mut f: impl AsyncFnMut() -> viewer::ViewerResult<T>,
+) -> anyhow::Result<T> {
+    loop {
+        match f().await {
+            Ok(output) => return Ok(output),
+            Err(Either::Left(ViewerError::OverCapacity)) => {
+                eprintln!("Too many requests");
+                tokio::time::sleep(BACKOFF).await;
+            }
+            Err(Either::Right(err)) => return Err(err),
+        }
+    }
+}
@@ -5,37 +5,273 @@ use hex::FromHex;
 use proc_macro2::Span;
 use quote::quote;
 use serde::Deserialize;
-use std::sync::LazyLock;
-use syn::LitInt;
+use std::{collections::HashMap, path::PathBuf};
+use syn::{Ident, LitInt, LitStr, parse::Parse};
 
+/// Number of colours a palette must have, as DNA stores codes in 6 bits.
+const PALETTE_SIZE: usize = 64;
+
+/// Palettes that can be referred to by name, with paths relative to the
+/// workspace root.
+const NAMED_PALETTES: &[(&str, &str)] = &[("canvas", "palette.json")];
+
+/// Builds a map from palette code to colour. Takes either the name of a
+/// known palette, `colors!(canvas)`, or a path relative to the calling
+/// crate, `colors!("../palette.json")`.
 #[proc_macro]
 pub fn colors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    colors_map(&input, false)
+    colors_map(input, false)
 }
 
+/// Builds a map from colour to palette code. Takes the same argument as
+/// `colors!`.
 #[proc_macro]
 pub fn codes(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    colors_map(&input, true)
+    colors_map(input, true)
+}
+
+/// Builds an array with a `PaletteEntry` for every code, in code order.
+/// Takes the same argument as `colors!`. Expects a struct in scope with
+/// `code: u8`, `rgb: [u8; 3]`, `hsl: [u16; 3]`, `name: &'static str`,
+/// `family: &'static str` and `shade: u8` fields. Palette files only hold
+/// colours, so `name` is just the capitalized family followed by the shade.
+#[proc_macro]
+pub fn entries(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    entries_array(input)
 }
 
 #[derive(Deserialize)]
 struct Data(Vec<String>);
 
-static CODES: LazyLock<Data> = LazyLock::new(|| {
-    let file = include_bytes!("../../palette.json");
-    serde_json::from_slice::<Data>(file).unwrap()
-});
+enum PaletteSource {
+    Name(Ident),
+    Path(LitStr),
+}
 
-fn colors_map(input: &proc_macro::TokenStream, inverse: bool) -> proc_macro::TokenStream {
-    if !input.is_empty() {
-        return syn::Error::new(Span::call_site(), "No inputs expected")
-            .into_compile_error()
-            .into();
+impl Parse for PaletteSource {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(Ident) {
+            input.parse().map(Self::Name)
+        } else if lookahead.peek(LitStr) {
+            input.parse().map(Self::Path)
+        } else {
+            Err(syn::Error::new(
+                input.span(),
+                "Expected a palette name or a path to a palette file",
+            ))
+        }
     }
+}
 
-    let items = CODES.0.iter().enumerate().map(|(index, color_hex)| {
-        let color_value = <[u8; 3]>::from_hex(color_hex).unwrap();
+impl PaletteSource {
+    fn span(&self) -> Span {
+        match self {
+            Self::Name(name) => name.span(),
+            Self::Path(path) => path.span(),
+        }
+    }
+
+    fn resolve(&self) -> syn::Result<PathBuf> {
+        match self {
+            Self::Name(name) => {
+                let name = name.to_string();
+                let Some((_, path)) = NAMED_PALETTES.iter().find(|(known, _)| *known == name)
+                else {
+                    let known = NAMED_PALETTES.iter().map(|(known, _)| *known);
+                    return Err(syn::Error::new(
+                        self.span(),
+                        format!(
+                            "Unknown palette `{name}`, expected one of: {}",
+                            known.collect::<Vec<_>>().join(", ")
+                        ),
+                    ));
+                };
+
+                Ok(PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("..")
+                    .join(path))
+            }
+            Self::Path(path) => {
+                let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+                    .map_err(|_| syn::Error::new(self.span(), "CARGO_MANIFEST_DIR is not set"))?;
+
+                Ok(PathBuf::from(manifest_dir).join(path.value()))
+            }
+        }
+    }
+}
+
+/// Reads a palette and checks that it has exactly `PALETTE_SIZE` unique,
+/// valid colours.
+fn load_palette(source: &PaletteSource) -> syn::Result<(PathBuf, Vec<[u8; 3]>)> {
+    let span = source.span();
+    let path = source.resolve()?;
+
+    let file = std::fs::read(&path).map_err(|err| {
+        syn::Error::new(span, format!("Failed to read {}: {err}", path.display()))
+    })?;
+
+    let Data(entries) = serde_json::from_slice(&file).map_err(|err| {
+        syn::Error::new(
+            span,
+            format!(
+                "{} is not a JSON array of hex colours: {err}",
+                path.display()
+            ),
+        )
+    })?;
+
+    if entries.len() != PALETTE_SIZE {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "Palette has {} colours, expected exactly {PALETTE_SIZE}",
+                entries.len()
+            ),
+        ));
+    }
+
+    let mut first_index = HashMap::new();
+    let mut colors = vec![];
+
+    for (index, entry) in entries.iter().enumerate() {
+        let color = <[u8; 3]>::from_hex(entry).map_err(|_| {
+            syn::Error::new(
+                span,
+                format!("Palette entry {index} `{entry}` is not a six-digit hex colour"),
+            )
+        })?;
+
+        if let Some(first) = first_index.insert(color, index) {
+            return Err(syn::Error::new(
+                span,
+                format!("Palette entry {index} `{entry}` duplicates entry {first}"),
+            ));
+        }
+
+        colors.push(color);
+    }
+
+    Ok((path, colors))
+}
+
+/// Hue in degrees, saturation and lightness in percent, all rounded.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_hsl(rgb: [u8; 3]) -> [u16; 3] {
+    let [r, g, b] = rgb.map(|channel| f64::from(channel) / 255.0);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let lightness = f64::midpoint(max, min);
+
+    let saturation = if delta == 0.0 {
+        0.0
+    } else {
+        delta / (1.0 - (2.0 * lightness - 1.0).abs())
+    };
 
+    let max_channel = rgb.into_iter().max().unwrap();
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if rgb[0] == max_channel {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if rgb[1] == max_channel {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    [
+        hue.round() as u16 % 360,
+        (saturation * 100.0).round() as u16,
+        (lightness * 100.0).round() as u16,
+    ]
+}
+
+/// Groups colours by hue, with barely saturated ones as grays.
+fn family([hue, saturation, lightness]: [u16; 3]) -> &'static str {
+    if saturation < 15 || !(6..=97).contains(&lightness) {
+        return "gray";
+    }
+
+    match hue {
+        0..15 | 345.. => "red",
+        15..45 => "orange",
+        45..70 => "yellow",
+        70..160 => "green",
+        160..195 => "teal",
+        195..255 => "blue",
+        255..290 => "purple",
+        _ => "pink",
+    }
+}
+
+fn entries_array(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = syn::parse_macro_input!(input as PaletteSource);
+
+    let (path, colors) = match load_palette(&source) {
+        Ok(palette) => palette,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    let hsl = colors.iter().copied().map(to_hsl).collect::<Vec<_>>();
+    let families = hsl.iter().copied().map(family).collect::<Vec<_>>();
+
+    let items = colors.iter().enumerate().map(|(index, rgb)| {
+        let [hue, saturation, lightness] = hsl[index];
+        let family = families[index];
+
+        // Shades count from the darkest colour of the family.
+        let shade = 1
+            + (0..colors.len())
+                .filter(|other| {
+                    families[*other] == family && (hsl[*other][2], *other) < (hsl[index][2], index)
+                })
+                .count();
+        let name = format!("{}{} {shade}", family[..1].to_uppercase(), &family[1..]);
+
+        let code = u8::try_from(index).unwrap();
+        let shade = u8::try_from(shade).unwrap();
+        let [red, green, blue] = rgb;
+
+        quote! {
+            PaletteEntry {
+                code: #code,
+                rgb: [#red, #green, #blue],
+                hsl: [#hue, #saturation, #lightness],
+                name: #name,
+                family: #family,
+                shade: #shade,
+            }
+        }
+    });
+
+    let path = path.to_string_lossy();
+
+    let output = quote! {
+        {
+            const _: &[u8] = include_bytes!(#path);
+
+            [ #( #items ),* ]
+        }
+    };
+
+    output.into()
+}
+
+fn colors_map(input: proc_macro::TokenStream, inverse: bool) -> proc_macro::TokenStream {
+    let source = syn::parse_macro_input!(input as PaletteSource);
+
+    let (path, colors) = match load_palette(&source) {
+        Ok(palette) => palette,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    let items = colors.iter().enumerate().map(|(index, color_value)| {
         let lit_int = |value| LitInt::new(&format!("{value}u8"), Span::call_site());
         let index_lit = lit_int(u8::try_from(index).unwrap());
         let ch1_lit = lit_int(color_value[0]);
@@ -53,9 +289,16 @@ fn colors_map(input: &proc_macro::TokenStream, inverse: bool) -> proc_macro::Tok
         }
     });
 
+    // Makes the calling crate rebuild when the palette file changes.
+    let path = path.to_string_lossy();
+
     let output = quote! {
-        ::phf::phf_map! {
-            #( #items ),*
+        {
+            const _: &[u8] = include_bytes!(#path);
+
+            ::phf::phf_map! {
+                #( #items ),*
+            }
         }
     };
 
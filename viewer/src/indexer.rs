@@ -0,0 +1,105 @@
+use crate::{NftData, NftItem, NftItemsResponse};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use tonlib_core::TonAddress;
+
+/// An item as the indexer stores and serves it.
+#[derive(Serialize, Deserialize)]
+pub struct IndexedItem {
+    pub dna: String,
+    pub dna_hash: String,
+    #[serde(flatten)]
+    pub data: NftData,
+}
+
+impl IndexedItem {
+    /// The item as `Viewer::get_items` lists it.
+    pub fn to_nft_item(&self) -> NftItem {
+        match &self.data.content {
+            Some(content) => NftItem::from_content(self.data.index, content),
+            None => NftItem {
+                index: self.data.index,
+                name: String::new(),
+                description: String::new(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DnaLookup {
+    pub exists: bool,
+    pub item_index: Option<u64>,
+    /// Whether every minted item was indexed, so that a miss is final.
+    pub is_complete: bool,
+}
+
+/// Parses a DNA hash in hex, with or without `0x` and leading zeros.
+pub fn parse_dna_hash(hash: &str) -> Option<[u8; 32]> {
+    let hash = hash.trim_start_matches("0x");
+
+    <[u8; 32]>::from_hex(format!("{hash:0>64}")).ok()
+}
+
+/// Client of the indexer, which mirrors collection items into a local store.
+#[derive(Clone)]
+pub struct Indexer {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Indexer {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Returns `None` for items that are not indexed yet.
+    pub async fn get_item(&self, item_index: u64) -> anyhow::Result<Option<IndexedItem>> {
+        let response = self
+            .client
+            .get(format!("{}/items/{item_index}", self.url))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+
+    /// Items of an owner in the same pages as `Viewer::get_items`.
+    pub async fn get_items(
+        &self,
+        owner_address: &TonAddress,
+        page: usize,
+    ) -> anyhow::Result<NftItemsResponse> {
+        Ok(self
+            .client
+            .get(format!("{}/items", self.url))
+            .query(&[
+                ("owner_address", owner_address.to_string()),
+                ("page", page.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub async fn lookup_dna(&self, hash: &[u8; 32]) -> anyhow::Result<DnaLookup> {
+        Ok(self
+            .client
+            .get(format!("{}/api/dna/lookup", self.url))
+            .query(&[("hash", hex::encode(hash))])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
@@ -8,10 +8,16 @@ use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::sync::{mpsc, oneshot};
 use tonlib_core::{
     TonAddress,
-    cell::{ArcCell, TonCellError},
+    cell::{ArcCell, CellParser, TonCellError},
     tlb_types::tlb::TLB,
 };
 
+pub mod indexer;
+pub mod item_address;
+
+/// Number of items in a page of `Viewer::get_items`.
+pub const PAGE_SIZE: usize = 10;
+
 #[derive(Clone)]
 pub struct Viewer {
     queue: mpsc::Sender<Job>,
@@ -26,19 +32,61 @@ struct Job {
 
 pub type ViewerResult<T> = Result<T, Either<ViewerError, anyhow::Error>>;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NftItemsResponse {
-    items: Vec<NftItem>,
-    has_next_page: bool,
+    pub items: Vec<NftItem>,
+    pub has_next_page: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NftItem {
     pub index: u64,
     pub name: String,
     pub description: String,
 }
 
+impl NftItem {
+    /// Builds the name and description from the item content the same way
+    /// `get_nft_content` in `collection.tact` does.
+    pub fn from_content(index: u64, content: &IndividualContent) -> Self {
+        let fingerprint = format!("{:0>64}", content.artist_fingerprint);
+        let last_resale_value = content.last_resale_value.unwrap_or(0);
+        let (whole, fraction) = (
+            last_resale_value / 1_000_000_000,
+            last_resale_value % 1_000_000_000,
+        );
+        let coins = if fraction == 0 {
+            whole.to_string()
+        } else {
+            format!("{whole}.{}", format!("{fraction:09}").trim_end_matches('0'))
+        };
+
+        Self {
+            index,
+            name: format!("\"{}\" by {}", content.title, content.artist),
+            description: format!(
+                "Artist fingerprint: {}\nLast sold for: {coins}ton",
+                &fingerprint[..8]
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NftData {
+    pub index: u64,
+    pub owner_address: TonAddress,
+    pub content: Option<IndividualContent>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IndividualContent {
+    pub title: String,
+    pub artist: String,
+    pub last_resale_value: Option<u64>,
+    pub artist_fingerprint: String,
+}
+
 impl Viewer {
     pub fn new(api_url: String, api_key: Option<String>) -> Self {
         #[derive(Deserialize)]
@@ -196,8 +244,6 @@ impl Viewer {
             page: Option<usize>,
         }
 
-        const PAGE_SIZE: usize = 10;
-
         impl Task for GetItems {
             type Output = NftItemsResponse;
 
@@ -424,6 +470,163 @@ impl Viewer {
         self.run_task(GetItemPrice { store_address }).await
     }
 
+    pub async fn get_next_item_index(&self, collection_address: TonAddress) -> ViewerResult<u64> {
+        struct GetNextItemIndex {
+            collection_address: TonAddress,
+        }
+
+        impl Task for GetNextItemIndex {
+            type Output = u64;
+
+            fn job_payload(&self) -> ((reqwest::Method, &'static str), serde_json::Value) {
+                let collection_address = self.collection_address.to_string();
+                let params = serde_json::json!({
+                    "address": collection_address,
+                    "method": "get_collection_data",
+                    "stack": []
+                });
+                ((reqwest::Method::POST, "runGetMethod"), params)
+            }
+
+            fn parse_output(output: serde_json::Value) -> Result<Self::Output, anyhow::Error> {
+                #[derive(Deserialize)]
+                struct Payload {
+                    stack: Option<Vec<StackElem>>,
+                }
+
+                #[derive(Deserialize)]
+                struct StackElem {
+                    #[serde(rename = "type")]
+                    type_: String,
+                    value: serde_json::Value,
+                }
+
+                let err_response = || anyhow::anyhow!("Invalid response from viewer");
+
+                let payload = serde_json::from_value::<Payload>(output)?;
+
+                if let Some(stack_item) = payload.stack.and_then(|stack| stack.into_iter().next())
+                    && stack_item.type_ == "num"
+                    && let Some(value) = stack_item.value.as_str()
+                {
+                    u64::from_str_radix(&value[2..], 16).map_err(|_| err_response())
+                } else {
+                    Err(err_response())
+                }
+            }
+        }
+
+        self.run_task(GetNextItemIndex { collection_address }).await
+    }
+
+    pub async fn get_nft_data(&self, item_address: TonAddress) -> ViewerResult<NftData> {
+        struct GetNftData {
+            item_address: TonAddress,
+        }
+
+        impl Task for GetNftData {
+            type Output = NftData;
+
+            fn job_payload(&self) -> ((reqwest::Method, &'static str), serde_json::Value) {
+                let item_address = self.item_address.to_string();
+                let params = serde_json::json!({
+                    "address": item_address,
+                    "method": "get_nft_data",
+                    "stack": []
+                });
+                ((reqwest::Method::POST, "runGetMethod"), params)
+            }
+
+            fn parse_output(output: serde_json::Value) -> Result<Self::Output, anyhow::Error> {
+                #[derive(Deserialize)]
+                struct Payload {
+                    stack: Option<Vec<StackElem>>,
+                }
+
+                #[derive(Deserialize)]
+                struct StackElem {
+                    #[serde(rename = "type")]
+                    type_: String,
+                    value: String,
+                }
+
+                let err_response = || anyhow::anyhow!("Invalid response from viewer");
+
+                let payload = serde_json::from_value::<Payload>(output)?;
+
+                let Some(
+                    [
+                        _is_initialized,
+                        index,
+                        _collection_address,
+                        owner_address,
+                        content,
+                    ],
+                ) = payload
+                    .stack
+                    .and_then(|stack| <[StackElem; 5]>::try_from(stack).ok())
+                else {
+                    return Err(err_response());
+                };
+
+                if index.type_ != "num" || owner_address.type_ != "slice" || content.type_ != "cell"
+                {
+                    return Err(err_response());
+                }
+
+                let index =
+                    u64::from_str_radix(&index.value[2..], 16).map_err(|_| err_response())?;
+
+                let owner_address = ArcCell::from_boc_b64(&owner_address.value)
+                    .and_then(|cell| cell.parser().load_address())
+                    .map_err(|err| anyhow::Error::new(err).context("failed to parse cell"))?;
+
+                let content = ArcCell::from_boc_b64(&content.value)
+                    .map_err(|err| anyhow::Error::new(err).context("failed to parse cell"))?;
+
+                let content = if content.bit_len() == 0 && content.references().is_empty() {
+                    None
+                } else {
+                    Some(
+                        content
+                            .parse_fully(|parser| {
+                                let title = load_string_ref(parser)?;
+                                let artist = load_string_ref(parser)?;
+
+                                let last_resale_value = if parser.load_bit()? {
+                                    Some(u64::try_from(parser.load_int(257)?).map_err(|err| {
+                                        TonCellError::InvalidInput(err.to_string())
+                                    })?)
+                                } else {
+                                    None
+                                };
+
+                                let artist_fingerprint = parser.load_int(257)?.to_str_radix(16);
+
+                                Ok(IndividualContent {
+                                    title,
+                                    artist,
+                                    last_resale_value,
+                                    artist_fingerprint,
+                                })
+                            })
+                            .map_err(|err| {
+                                anyhow::Error::new(err).context("failed to parse cell")
+                            })?,
+                    )
+                };
+
+                Ok(NftData {
+                    index,
+                    owner_address,
+                    content,
+                })
+            }
+        }
+
+        self.run_task(GetNftData { item_address }).await
+    }
+
     async fn run_task<T: Task>(&self, task: T) -> ViewerResult<T::Output> {
         let (callback_tx, callback_rx) = oneshot::channel();
         let (method, params) = task.job_payload();
@@ -449,6 +652,24 @@ impl Viewer {
     }
 }
 
+fn load_string_ref(parser: &mut CellParser) -> Result<String, TonCellError> {
+    let mut cell = parser.next_reference()?;
+    let mut bytes = vec![];
+
+    loop {
+        let mut parser = cell.parser();
+        let num_bytes = parser.remaining_bytes();
+        bytes.extend(parser.load_bytes(num_bytes)?);
+
+        let Some(next) = cell.references().first() else {
+            break;
+        };
+        cell = next.clone();
+    }
+
+    String::from_utf8(bytes).map_err(|err| TonCellError::InvalidInput(err.to_string()))
+}
+
 trait Task {
     type Output;
 
@@ -0,0 +1,431 @@
+use crate::{DirtyRect, history::History};
+use itertools::Itertools;
+
+pub const SIZE: usize = 64;
+
+/// Code of pixels that let the layers below show through. It is outside of
+/// the 64 palette codes and never reaches DNA.
+pub const TRANSPARENT: u8 = 64;
+
+/// How far off the grid line endpoints can be. Lines are walked pixel by
+/// pixel, so this bounds their length.
+pub const MAX_REACH: i32 = 1024;
+
+/// Whether layers can hold a code: a palette code or `TRANSPARENT`.
+pub fn is_layer_code(code: u8) -> bool {
+    code <= TRANSPARENT
+}
+
+/// The 64×64 grid of palette codes, indexed by row then column like the
+/// `number[][]` data everywhere else.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Grid(pub [[u8; SIZE]; SIZE]);
+
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    /// Locked layers ignore every drawing operation.
+    pub locked: bool,
+    pub grid: Grid,
+    pub history: History,
+}
+
+impl Layer {
+    pub fn new(name: String, grid: Grid) -> Self {
+        Self {
+            name,
+            visible: true,
+            locked: false,
+            grid,
+            history: History::default(),
+        }
+    }
+}
+
+/// A rectangular block of codes cut out of a grid.
+#[derive(Clone)]
+pub struct Region {
+    pub width: usize,
+    pub height: usize,
+    pub codes: Vec<u8>,
+}
+
+/// Bounding box of the pixels an operation actually changed.
+#[derive(Default)]
+pub struct Dirty(Option<(usize, usize, usize, usize)>);
+
+impl Dirty {
+    pub fn add(&mut self, x: usize, y: usize) {
+        self.0 = Some(match self.0 {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+
+    pub fn into_rect(self) -> Option<DirtyRect> {
+        let (x0, y0, x1, y1) = self.0?;
+        let coordinate = |value: usize| u32::try_from(value).unwrap();
+
+        Some(DirtyRect {
+            x: coordinate(x0),
+            y: coordinate(y0),
+            width: coordinate(x1 - x0 + 1),
+            height: coordinate(y1 - y0 + 1),
+        })
+    }
+}
+
+impl Grid {
+    pub fn filled(code: u8) -> Self {
+        Self([[code; SIZE]; SIZE])
+    }
+
+    pub fn from_data(data: &[Vec<u8>]) -> Option<Self> {
+        let mut grid = Self::filled(0);
+
+        if data.len() != SIZE {
+            return None;
+        }
+
+        for (row, codes) in grid.0.iter_mut().zip(data) {
+            if !codes.iter().copied().all(is_layer_code) {
+                return None;
+            }
+            *row = codes.as_slice().try_into().ok()?;
+        }
+
+        Some(grid)
+    }
+
+    pub fn to_data(&self) -> Vec<Vec<u8>> {
+        self.0.iter().map(|row| row.to_vec()).collect()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        self.0.get(y)?.get(x).copied()
+    }
+
+    /// Writes a code if the coordinates are on the grid, recording the pixel
+    /// as dirty when its code changes.
+    fn put(&mut self, x: i32, y: i32, code: u8, dirty: &mut Dirty) {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return;
+        };
+
+        if let Some(pixel) = self.0.get_mut(y).and_then(|row| row.get_mut(x))
+            && *pixel != code
+        {
+            *pixel = code;
+            dirty.add(x, y);
+        }
+    }
+
+    /// Replaces the whole grid, marking every pixel that differs as dirty.
+    fn replace(&mut self, next: Self) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        for (y, x) in (0..SIZE).cartesian_product(0..SIZE) {
+            if self.0[y][x] != next.0[y][x] {
+                dirty.add(x, y);
+            }
+        }
+
+        *self = next;
+        dirty.into_rect()
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, code: u8) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+        self.put(x, y, code, &mut dirty);
+        dirty.into_rect()
+    }
+
+    /// Fills the area of the same code around `(x, y)`, through diagonals
+    /// too when `diagonal` is set.
+    pub fn flood_fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        code: u8,
+        diagonal: bool,
+    ) -> Option<DirtyRect> {
+        let target = self.get(x, y)?;
+        if target == code {
+            return None;
+        }
+
+        let mut dirty = Dirty::default();
+        let mut stack = vec![(x, y)];
+
+        while let Some((x, y)) = stack.pop() {
+            if self.0[y][x] != target {
+                continue;
+            }
+
+            self.0[y][x] = code;
+            dirty.add(x, y);
+
+            for (dx, dy) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ]
+            .into_iter()
+            .take(if diagonal { 8 } else { 4 })
+            {
+                if let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                    && nx < SIZE
+                    && ny < SIZE
+                {
+                    stack.push((nx, ny));
+                }
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    /// Draws a one pixel wide line with Bresenham's algorithm. Lines with an
+    /// endpoint more than `MAX_REACH` off the grid draw nothing.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, code: u8) -> Option<DirtyRect> {
+        let reach = -MAX_REACH..i32::try_from(SIZE).unwrap() + MAX_REACH;
+        if ![x0, y0, x1, y1]
+            .iter()
+            .all(|coordinate| reach.contains(coordinate))
+        {
+            return None;
+        }
+
+        let mut dirty = Dirty::default();
+
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+
+        loop {
+            self.put(x, y, code, &mut dirty);
+
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    /// Draws the rectangle with corners `(x0, y0)` and `(x1, y1)`, inclusive.
+    pub fn rectangle(
+        &mut self,
+        (x0, y0): (i32, i32),
+        (x1, y1): (i32, i32),
+        code: u8,
+        filled: bool,
+    ) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        let (left, right) = (x0.min(x1), x0.max(x1));
+        let (top, bottom) = (y0.min(y1), y0.max(y1));
+
+        for (y, x) in clip(top, bottom).cartesian_product(clip(left, right)) {
+            if filled || x == left || x == right || y == top || y == bottom {
+                self.put(x, y, code, &mut dirty);
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    /// Draws the ellipse inscribed in the rectangle with corners `(x0, y0)`
+    /// and `(x1, y1)`. The outline is made of the inside pixels that touch an
+    /// outside one, so it stays closed at any size.
+    pub fn ellipse(
+        &mut self,
+        (x0, y0): (i32, i32),
+        (x1, y1): (i32, i32),
+        code: u8,
+        filled: bool,
+    ) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        let (left, right) = (x0.min(x1), x0.max(x1));
+        let (top, bottom) = (y0.min(y1), y0.max(y1));
+
+        let center_x = f64::midpoint(f64::from(left), f64::from(right));
+        let center_y = f64::midpoint(f64::from(top), f64::from(bottom));
+        let radius_x = (f64::from(right) - f64::from(left)) / 2.0 + 0.5;
+        let radius_y = (f64::from(bottom) - f64::from(top)) / 2.0 + 0.5;
+
+        let is_inside = |x: i32, y: i32| {
+            let dx = (f64::from(x) - center_x) / radius_x;
+            let dy = (f64::from(y) - center_y) / radius_y;
+            dx * dx + dy * dy <= 1.0
+        };
+
+        for (y, x) in clip(top, bottom).cartesian_product(clip(left, right)) {
+            if !is_inside(x, y) {
+                continue;
+            }
+
+            let is_edge = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .into_iter()
+                .any(|(dx, dy)| !is_inside(x + dx, y + dy));
+
+            if filled || is_edge {
+                self.put(x, y, code, &mut dirty);
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    pub fn flip_horizontal(&mut self) -> Option<DirtyRect> {
+        let mut next = self.clone();
+        for row in &mut next.0 {
+            row.reverse();
+        }
+        self.replace(next)
+    }
+
+    pub fn flip_vertical(&mut self) -> Option<DirtyRect> {
+        let mut next = self.clone();
+        next.0.reverse();
+        self.replace(next)
+    }
+
+    /// Reflects the left half onto the right half.
+    pub fn mirror_horizontal(&mut self) -> Option<DirtyRect> {
+        let mut next = self.clone();
+        for row in &mut next.0 {
+            for x in 0..SIZE / 2 {
+                row[SIZE - 1 - x] = row[x];
+            }
+        }
+        self.replace(next)
+    }
+
+    /// Reflects the top half onto the bottom half.
+    pub fn mirror_vertical(&mut self) -> Option<DirtyRect> {
+        let mut next = self.clone();
+        for y in 0..SIZE / 2 {
+            next.0[SIZE - 1 - y] = next.0[y];
+        }
+        self.replace(next)
+    }
+
+    /// Rotates by a number of clockwise quarter turns, negative for counter
+    /// clockwise.
+    pub fn rotate(&mut self, quarter_turns: i32) -> Option<DirtyRect> {
+        let mut next = self.clone();
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            let previous = next.clone();
+            for (y, x) in (0..SIZE).cartesian_product(0..SIZE) {
+                next.0[y][x] = previous.0[SIZE - 1 - x][y];
+            }
+        }
+        self.replace(next)
+    }
+
+    /// Moves every pixel by `(dx, dy)`, wrapping around the edges.
+    pub fn shift(&mut self, dx: i32, dy: i32) -> Option<DirtyRect> {
+        let size = i32::try_from(SIZE).unwrap();
+        let (dx, dy) = (
+            usize::try_from(dx.rem_euclid(size)).unwrap(),
+            usize::try_from(dy.rem_euclid(size)).unwrap(),
+        );
+
+        let mut next = self.clone();
+        for (y, x) in (0..SIZE).cartesian_product(0..SIZE) {
+            next.0[(y + dy) % SIZE][(x + dx) % SIZE] = self.0[y][x];
+        }
+        self.replace(next)
+    }
+
+    /// Draws the pixels of `upper` that are not transparent over this grid.
+    pub fn merge(&mut self, upper: &Self) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        for (y, x) in (0..SIZE).cartesian_product(0..SIZE) {
+            let code = upper.0[y][x];
+            if code != TRANSPARENT && self.0[y][x] != code {
+                self.0[y][x] = code;
+                dirty.add(x, y);
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    pub fn replace_color(&mut self, from: u8, to: u8) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        for (y, x) in (0..SIZE).cartesian_product(0..SIZE) {
+            if self.0[y][x] == from && from != to {
+                self.0[y][x] = to;
+                dirty.add(x, y);
+            }
+        }
+
+        dirty.into_rect()
+    }
+
+    /// Copies the part of the rectangle at `(x, y)` that lies on the grid.
+    pub fn copy(&self, x: usize, y: usize, width: usize, height: usize) -> Region {
+        let (x, y) = (x.min(SIZE), y.min(SIZE));
+        let (width, height) = (width.min(SIZE - x), height.min(SIZE - y));
+
+        Region {
+            width,
+            height,
+            codes: (y..y + height)
+                .cartesian_product(x..x + width)
+                .map(|(y, x)| self.0[y][x])
+                .collect(),
+        }
+    }
+
+    /// Pastes a region with its top left corner at `(x, y)`, clipping what
+    /// falls off the grid. Pixels of the `transparent` code are skipped.
+    pub fn paste(
+        &mut self,
+        region: &Region,
+        x: i32,
+        y: i32,
+        transparent: Option<u8>,
+    ) -> Option<DirtyRect> {
+        let mut dirty = Dirty::default();
+
+        for (index, code) in region.codes.iter().enumerate() {
+            if Some(*code) == transparent {
+                continue;
+            }
+
+            let dx = i32::try_from(index % region.width).unwrap();
+            let dy = i32::try_from(index / region.width).unwrap();
+            self.put(x + dx, y + dy, *code, &mut dirty);
+        }
+
+        dirty.into_rect()
+    }
+}
+
+/// The part of the inclusive range `start..=end` that lies on the grid.
+fn clip(start: i32, end: i32) -> std::ops::RangeInclusive<i32> {
+    start.max(0)..=end.min(i32::try_from(SIZE).unwrap() - 1)
+}
@@ -0,0 +1,332 @@
+use crate::{
+    MAX_LAYERS,
+    canvas::{Grid, SIZE, TRANSPARENT},
+};
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"CVDR";
+const VERSION: u8 = 1;
+
+/// Palettes a draft can be drawn with. Only the current collection's exists
+/// so far.
+pub const CANVAS_PALETTE: u8 = 0;
+
+const HAS_TITLE: u8 = 1;
+const HAS_ARTIST: u8 = 2;
+
+const LAYER_VISIBLE: u8 = 1;
+const LAYER_LOCKED: u8 = 2;
+
+const RLE: u8 = 0;
+const DEFLATE: u8 = 1;
+
+#[derive(Clone)]
+pub struct Draft {
+    pub palette: u8,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+    pub grid: Grid,
+}
+
+pub struct InvalidDraft;
+
+impl Draft {
+    /// Layout, all numbers big endian:
+    ///
+    /// - magic `CVDR`, version, palette id, flags for title and artist
+    /// - title and artist, if present, as a `u16` length and UTF-8
+    /// - layer count, then for each layer its flags, name, compression and
+    ///   the `u16` length of the compressed codes
+    ///
+    /// Codes are run-length encoded or deflated, whichever is shorter.
+    /// Returns `None` if a title, artist or layer name is longer than the
+    /// 65535 bytes its length fits.
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.palette);
+        let mut flags = 0;
+        if self.title.is_some() {
+            flags |= HAS_TITLE;
+        }
+        if self.artist.is_some() {
+            flags |= HAS_ARTIST;
+        }
+        bytes.push(flags);
+
+        for text in [&self.title, &self.artist].into_iter().flatten() {
+            write_string(&mut bytes, text)?;
+        }
+
+        bytes.push(u8::try_from(self.layers.len()).unwrap());
+
+        for layer in &self.layers {
+            let mut flags = 0;
+            if layer.visible {
+                flags |= LAYER_VISIBLE;
+            }
+            if layer.locked {
+                flags |= LAYER_LOCKED;
+            }
+            bytes.push(flags);
+            write_string(&mut bytes, &layer.name)?;
+
+            let codes = layer.grid.0.as_flattened();
+            let (compression, payload) = [(RLE, rle(codes)), (DEFLATE, deflate(codes))]
+                .into_iter()
+                .min_by_key(|(_, payload)| payload.len())
+                .unwrap();
+
+            bytes.push(compression);
+            bytes.extend(u16::try_from(payload.len()).unwrap().to_be_bytes());
+            bytes.extend(payload);
+        }
+
+        Some(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, InvalidDraft> {
+        let mut reader = Reader(bytes.strip_prefix(MAGIC).ok_or(InvalidDraft)?);
+
+        // Versions only ever get added, each with its own branch here.
+        match reader.u8()? {
+            1 => decode_v1(reader),
+            _ => Err(InvalidDraft),
+        }
+    }
+}
+
+fn decode_v1(mut reader: Reader) -> Result<Draft, InvalidDraft> {
+    let palette = reader.u8()?;
+    if palette != CANVAS_PALETTE {
+        return Err(InvalidDraft);
+    }
+
+    let flags = reader.u8()?;
+    let title = (flags & HAS_TITLE != 0)
+        .then(|| reader.string())
+        .transpose()?;
+    let artist = (flags & HAS_ARTIST != 0)
+        .then(|| reader.string())
+        .transpose()?;
+
+    // Canvases keep at least one layer, and never more than they can add.
+    let layer_count = usize::from(reader.u8()?);
+    if layer_count == 0 || layer_count > MAX_LAYERS {
+        return Err(InvalidDraft);
+    }
+
+    let mut layers = vec![];
+
+    for _ in 0..layer_count {
+        let flags = reader.u8()?;
+        let name = reader.string()?;
+        let compression = reader.u8()?;
+        let length = usize::from(u16::from_be_bytes([reader.u8()?, reader.u8()?]));
+        let payload = reader.take(length)?;
+
+        let codes = match compression {
+            RLE => unrle(payload)?,
+            DEFLATE => inflate(payload)?,
+            _ => return Err(InvalidDraft),
+        };
+
+        if codes.len() != SIZE * SIZE || codes.iter().any(|code| *code > TRANSPARENT) {
+            return Err(InvalidDraft);
+        }
+
+        let mut grid = Grid::filled(0);
+        grid.0.as_flattened_mut().copy_from_slice(&codes);
+
+        layers.push(Layer {
+            name,
+            visible: flags & LAYER_VISIBLE != 0,
+            locked: flags & LAYER_LOCKED != 0,
+            grid,
+        });
+    }
+
+    if !reader.0.is_empty() {
+        return Err(InvalidDraft);
+    }
+
+    Ok(Draft {
+        palette,
+        title,
+        artist,
+        layers,
+    })
+}
+
+fn write_string(bytes: &mut Vec<u8>, text: &str) -> Option<()> {
+    bytes.extend(u16::try_from(text.len()).ok()?.to_be_bytes());
+    bytes.extend(text.as_bytes());
+    Some(())
+}
+
+/// Pairs of run length, 1 to 255, and code.
+fn rle(codes: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for run in codes.chunk_by(|a, b| a == b) {
+        for chunk in run.chunks(usize::from(u8::MAX)) {
+            bytes.push(u8::try_from(chunk.len()).unwrap());
+            bytes.push(chunk[0]);
+        }
+    }
+
+    bytes
+}
+
+fn unrle(bytes: &[u8]) -> Result<Vec<u8>, InvalidDraft> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(InvalidDraft);
+    }
+
+    let mut codes = vec![];
+
+    for pair in bytes.chunks_exact(2) {
+        codes.extend(std::iter::repeat_n(pair[1], usize::from(pair[0])));
+        if codes.len() > SIZE * SIZE {
+            return Err(InvalidDraft);
+        }
+    }
+
+    Ok(codes)
+}
+
+fn deflate(codes: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    encoder.write_all(codes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn inflate(bytes: &[u8]) -> Result<Vec<u8>, InvalidDraft> {
+    let mut codes = vec![];
+
+    DeflateDecoder::new(bytes)
+        .take(u64::try_from(SIZE * SIZE + 1).unwrap())
+        .read_to_end(&mut codes)
+        .map_err(|_| InvalidDraft)?;
+
+    Ok(codes)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], InvalidDraft> {
+        if self.0.len() < n {
+            return Err(InvalidDraft);
+        }
+
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidDraft> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn string(&mut self) -> Result<String, InvalidDraft> {
+        let length = usize::from(u16::from_be_bytes([self.u8()?, self.u8()?]));
+        let bytes = self.take(length)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| InvalidDraft)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft() -> Draft {
+        let mut grid = Grid::filled(TRANSPARENT);
+        for (y, row) in grid.0.iter_mut().enumerate() {
+            for (x, code) in row.iter_mut().enumerate().take(y) {
+                *code = u8::try_from((x * y) % 64).unwrap();
+            }
+        }
+
+        Draft {
+            palette: CANVAS_PALETTE,
+            title: Some("Sunset ☀".into()),
+            artist: None,
+            layers: vec![
+                Layer {
+                    name: "Background".into(),
+                    visible: true,
+                    locked: true,
+                    grid: Grid::filled(12),
+                },
+                Layer {
+                    name: String::new(),
+                    visible: false,
+                    locked: false,
+                    grid,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let draft = draft();
+        let Ok(decoded) = Draft::decode(&draft.encode().unwrap()) else {
+            panic!("draft does not decode");
+        };
+
+        assert_eq!(decoded.title, draft.title);
+        assert_eq!(decoded.artist, draft.artist);
+        assert_eq!(decoded.layers.len(), draft.layers.len());
+        for (decoded, layer) in decoded.layers.iter().zip(&draft.layers) {
+            assert_eq!(decoded.name, layer.name);
+            assert_eq!(decoded.visible, layer.visible);
+            assert_eq!(decoded.locked, layer.locked);
+            assert!(decoded.grid == layer.grid);
+        }
+    }
+
+    #[test]
+    fn drafts_with_no_or_too_many_layers_do_not_decode() {
+        let mut draft = draft();
+        draft.layers.clear();
+        assert!(Draft::decode(&draft.encode().unwrap()).is_err());
+
+        let mut draft = self::draft();
+        let layer = draft.layers[1].clone();
+        draft.layers.resize(MAX_LAYERS, layer.clone());
+        assert!(Draft::decode(&draft.encode().unwrap()).is_ok());
+
+        draft.layers.push(layer);
+        assert!(Draft::decode(&draft.encode().unwrap()).is_err());
+    }
+
+    #[test]
+    fn long_text_does_not_encode() {
+        let mut draft = draft();
+        draft.artist = Some("a".repeat(usize::from(u16::MAX)));
+        assert!(draft.encode().is_some());
+
+        draft.layers[1].name = "a".repeat(usize::from(u16::MAX) + 1);
+        assert!(draft.encode().is_none());
+    }
+
+    #[test]
+    fn cut_drafts_do_not_decode() {
+        let bytes = draft().encode().unwrap();
+        for length in 0..bytes.len() {
+            assert!(Draft::decode(&bytes[..length]).is_err());
+        }
+    }
+}
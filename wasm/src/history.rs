@@ -0,0 +1,317 @@
+use crate::{
+    DirtyRect,
+    canvas::{Dirty, Grid, SIZE, TRANSPARENT},
+};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Oldest steps are dropped past this many, to keep the serialized history
+/// small enough for `localStorage`.
+const MAX_STEPS: usize = 256;
+
+const MAGIC: &[u8; 4] = b"CVHS";
+const VERSION: u8 = 1;
+
+/// A pixel that an undo step changed, as its index in the grid and its codes
+/// before and after.
+#[derive(Clone, Copy)]
+struct Change {
+    index: u16,
+    old: u8,
+    new: u8,
+}
+
+impl Change {
+    fn coordinates(self) -> (usize, usize) {
+        let index = usize::from(self.index);
+        (index % SIZE, index / SIZE)
+    }
+
+    /// Packs the 12 bit index and both 7 bit codes, which leave room for the
+    /// transparent code, into four bytes.
+    fn to_bytes(self) -> [u8; 4] {
+        (u32::from(self.index) << 14 | u32::from(self.old) << 7 | u32::from(self.new)).to_be_bytes()
+    }
+
+    fn from_bytes(bytes: [u8; 4]) -> Self {
+        let packed = u32::from_be_bytes(bytes);
+        let code = |shift: u32| u8::try_from(packed >> shift & 0x7f).unwrap();
+
+        Self {
+            index: u16::try_from(packed >> 14 & 0xfff).unwrap(),
+            old: code(7),
+            new: code(0),
+        }
+    }
+}
+
+type Step = Vec<Change>;
+
+/// Undo and redo stacks of sparse diffs. Changes accumulate into a pending
+/// step until it is committed, so that a whole stroke undoes at once.
+#[derive(Default, Clone)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    pending: BTreeMap<u16, (u8, u8)>,
+}
+
+pub struct InvalidHistory;
+
+impl History {
+    /// Records the pixels within `rect` that differ between `before` and
+    /// `after` into the pending step.
+    pub fn record(&mut self, before: &Grid, after: &Grid, rect: DirtyRect) {
+        let xs = rect.x as usize..(rect.x + rect.width) as usize;
+        let ys = rect.y as usize..(rect.y + rect.height) as usize;
+
+        for (y, x) in ys.cartesian_product(xs) {
+            let (old, new) = (before.0[y][x], after.0[y][x]);
+            if old == new {
+                continue;
+            }
+
+            let index = u16::try_from(y * SIZE + x).unwrap();
+            let entry = self.pending.entry(index).or_insert((old, new));
+            entry.1 = new;
+
+            if entry.0 == entry.1 {
+                self.pending.remove(&index);
+            }
+        }
+
+        self.redo.clear();
+    }
+
+    /// Closes the pending step, if it changed anything.
+    pub fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let step = std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|(index, (old, new))| Change { index, old, new })
+            .collect();
+
+        self.undo.push(step);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.pending.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, grid: &mut Grid) -> Option<DirtyRect> {
+        self.commit();
+        let step = self.undo.pop()?;
+        let rect = apply(grid, &step, |change| change.old);
+        self.redo.push(step);
+        rect
+    }
+
+    pub fn redo(&mut self, grid: &mut Grid) -> Option<DirtyRect> {
+        let step = self.redo.pop()?;
+        let rect = apply(grid, &step, |change| change.new);
+        self.undo.push(step);
+        rect
+    }
+}
+
+/// Serializes the stacks of every layer, committing pending steps first.
+pub fn save<'a>(histories: impl ExactSizeIterator<Item = &'a mut History>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.push(u8::try_from(histories.len()).unwrap());
+
+    for history in histories {
+        history.commit();
+
+        for stack in [&history.undo, &history.redo] {
+            bytes.extend(u16::try_from(stack.len()).unwrap().to_be_bytes());
+
+            for step in stack {
+                bytes.extend(u16::try_from(step.len()).unwrap().to_be_bytes());
+                bytes.extend(step.iter().flat_map(|change| change.to_bytes()));
+            }
+        }
+    }
+
+    bytes
+}
+
+/// Reads the histories of every layer. Stacks longer than `MAX_STEPS`, empty
+/// steps and codes that layers can't hold are rejected, as the bytes may
+/// have been tampered with.
+pub fn load(bytes: &[u8]) -> Result<Vec<History>, InvalidHistory> {
+    let rest = bytes.strip_prefix(MAGIC).ok_or(InvalidHistory)?;
+    let (&version, mut rest) = rest.split_first().ok_or(InvalidHistory)?;
+
+    let mut take = |length: usize| {
+        if rest.len() < length {
+            return Err(InvalidHistory);
+        }
+        let (taken, remaining) = rest.split_at(length);
+        rest = remaining;
+        Ok(taken)
+    };
+
+    if version != VERSION {
+        return Err(InvalidHistory);
+    }
+
+    let layers = take(1)?[0];
+
+    let mut histories = vec![];
+
+    for _ in 0..layers {
+        let mut stacks = [vec![], vec![]];
+
+        for stack in &mut stacks {
+            let steps = usize::from(u16::from_be_bytes(take(2)?.try_into().unwrap()));
+            if steps > MAX_STEPS {
+                return Err(InvalidHistory);
+            }
+
+            for _ in 0..steps {
+                let changes = usize::from(u16::from_be_bytes(take(2)?.try_into().unwrap()));
+                if changes == 0 {
+                    return Err(InvalidHistory);
+                }
+
+                let step = take(changes * 4)?
+                    .chunks_exact(4)
+                    .map(|change| Change::from_bytes(change.try_into().unwrap()))
+                    .collect::<Step>();
+                if step
+                    .iter()
+                    .any(|change| change.old > TRANSPARENT || change.new > TRANSPARENT)
+                {
+                    return Err(InvalidHistory);
+                }
+                stack.push(step);
+            }
+        }
+
+        let [undo, redo] = stacks;
+        histories.push(History {
+            undo,
+            redo,
+            pending: BTreeMap::new(),
+        });
+    }
+
+    if !rest.is_empty() {
+        return Err(InvalidHistory);
+    }
+
+    Ok(histories)
+}
+
+fn apply(grid: &mut Grid, step: &Step, code: impl Fn(Change) -> u8) -> Option<DirtyRect> {
+    let mut dirty = Dirty::default();
+
+    for change in step {
+        let (x, y) = change.coordinates();
+        grid.0[y][x] = code(*change);
+        dirty.add(x, y);
+    }
+
+    dirty.into_rect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws `code` at each pixel as its own step.
+    fn draw(history: &mut History, grid: &mut Grid, pixels: &[(i32, i32, u8)]) {
+        for &(x, y, code) in pixels {
+            let before = grid.clone();
+            let rect = grid.set(x, y, code).unwrap();
+            history.record(&before, grid, rect);
+            history.commit();
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut grids = [Grid::filled(0), Grid::filled(TRANSPARENT)];
+        let mut histories = [History::default(), History::default()];
+
+        draw(
+            &mut histories[0],
+            &mut grids[0],
+            &[(0, 0, 5), (63, 63, 63), (10, 20, 5)],
+        );
+        draw(&mut histories[1], &mut grids[1], &[(1, 2, 7), (1, 2, 0)]);
+        histories[1].undo(&mut grids[1]);
+
+        let bytes = save(histories.iter_mut());
+        let Ok(mut loaded) = load(&bytes) else {
+            panic!("history does not load");
+        };
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(save(loaded.iter_mut()), bytes);
+
+        // The loaded stacks take the grids back to where drawing started.
+        for (history, grid) in loaded.iter_mut().zip(&mut grids) {
+            while history.undo(grid).is_some() {}
+        }
+        assert!(grids[0] == Grid::filled(0));
+        assert!(grids[1] == Grid::filled(TRANSPARENT));
+
+        assert!(loaded[1].redo(&mut grids[1]).is_some());
+        assert_eq!(grids[1].get(1, 2), Some(7));
+    }
+
+    #[test]
+    fn damaged_histories_do_not_load() {
+        let mut history = History::default();
+        draw(&mut history, &mut Grid::filled(0), &[(3, 4, 9)]);
+        let bytes = save(std::iter::once(&mut history));
+
+        for length in 0..bytes.len() {
+            assert!(load(&bytes[..length]).is_err());
+        }
+
+        let mut other_version = bytes.clone();
+        other_version[MAGIC.len()] = VERSION + 1;
+        assert!(load(&other_version).is_err());
+    }
+
+    #[test]
+    fn tampered_histories_do_not_load() {
+        let mut history = History::default();
+        draw(&mut history, &mut Grid::filled(0), &[(3, 4, 9)]);
+        let bytes = save(std::iter::once(&mut history));
+
+        // The new code of the only change is in the low 7 bits of the last
+        // byte.
+        let mut out_of_palette = bytes.clone();
+        *out_of_palette.last_mut().unwrap() |= 0x7f;
+        assert!(load(&out_of_palette).is_err());
+
+        let mut empty_step = MAGIC.to_vec();
+        empty_step.extend([VERSION, 1, 0, 1, 0, 0, 0, 0]);
+        assert!(load(&empty_step).is_err());
+
+        let mut too_many_steps = MAGIC.to_vec();
+        too_many_steps.push(VERSION);
+        too_many_steps.push(1);
+        too_many_steps.extend(u16::try_from(MAX_STEPS + 1).unwrap().to_be_bytes());
+        for _ in 0..=MAX_STEPS {
+            too_many_steps.extend([0, 1]);
+            too_many_steps.extend(bytes[bytes.len() - 4..].iter());
+        }
+        too_many_steps.extend([0, 0]);
+        assert!(load(&too_many_steps).is_err());
+    }
+}
@@ -2,37 +2,770 @@
 #![warn(clippy::pedantic)]
 
 use itertools::Itertools;
-use js_sys::{Array, Number, Uint8Array};
+use js_sys::{Array, BigInt, Number, Uint8Array};
 use num_traits::ToPrimitive;
-use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
+use wasm_bindgen::{JsError, JsValue, prelude::wasm_bindgen};
 
-use crate::dna::Dna;
+use canvas_dna::{Dna, DnaCellError, DnaLinkError};
 
-mod dna;
+mod canvas;
+mod color;
+mod draft;
+mod history;
 mod pack;
+mod palette;
+mod palette_file;
 mod parse;
+mod project;
+mod quantize;
 mod render;
+mod resize;
 
 #[wasm_bindgen(typescript_custom_section)]
 const PARSE_IMAGE_RESPONSE_TYPEDEF: &'static str = r#"
 export type ParseImageResponse = {
   status: "ok",
-  data: number[][], 
+  data: number[][],
+  quantization?: QuantizationReport,
+  background_pixels: number,
+  unmappable_colors?: string[],
 } | {
-  status: "unsupported_extension" | "decode_error" | "wrong_dimensions" | "wrong_palette"
+  status: "wrong_palette",
+  wrong_colors: WrongPaletteColor[],
+  unmappable_colors?: string[],
+} | {
+  status: "unsupported_extension" | "decode_error" | "wrong_dimensions" | "invalid_background"
 }"#;
 
 #[wasm_bindgen(skip_typescript, getter_with_clone)]
 pub struct ParseImageResponse {
     pub status: String,
     pub data: JsValue,
+    pub quantization: Option<QuantizationReport>,
+    /// Number of source pixels that were transparent enough to be replaced
+    /// with `background`.
+    pub background_pixels: Option<u32>,
+    /// Colours of a project file palette that have no palette code.
+    pub unmappable_colors: JsValue,
+    /// Distinct colours missing from the palette, in order of appearance.
+    pub wrong_colors: JsValue,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct WrongPaletteColor {
+    /// Hex code of the offending colour.
+    pub color: String,
+    /// Number of pixels with this colour.
+    pub count: u32,
+    /// Coordinates of the first pixel with this colour.
+    pub x: u32,
+    pub y: u32,
+    /// Code of the perceptually nearest palette colour.
+    pub nearest: u8,
+    /// CIEDE2000 distance to the nearest palette colour.
+    pub distance: f64,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default)]
+pub struct ParseImageOptions {
+    /// Map colours missing from the palette to the perceptually nearest
+    /// palette colour instead of failing with `wrong_palette`.
+    pub quantize: bool,
+    /// Dithering to apply while quantizing. Anything but `None` implies
+    /// `quantize`.
+    pub dithering: Dithering,
+    /// How to bring images that are not 64×64 to size. With `None` only exact
+    /// integer upscales are accepted, anything else fails with
+    /// `wrong_dimensions`. Images with a side over 4096 pixels always fail
+    /// with it.
+    pub resize: Resize,
+    /// Palette code used for transparent pixels and to fill the margins left
+    /// by `Resize::Pad`.
+    pub background: u8,
+    /// Pixels with an alpha at or below this value are replaced with
+    /// `background`, the rest are taken as opaque. The default only replaces
+    /// fully transparent pixels.
+    pub alpha_threshold: u8,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default)]
+pub enum Dithering {
+    #[default]
+    None,
+    FloydSteinberg,
+    Atkinson,
+    Bayer,
+}
+
+#[wasm_bindgen]
+impl ParseImageOptions {
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default)]
+pub enum Resize {
+    #[default]
+    None,
+    /// Keep the aspect ratio and cut off what does not fit the square.
+    Crop,
+    /// Stretch the whole image to the square.
+    Fit,
+    /// Keep the aspect ratio and fill the margins with `background`.
+    Pad,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct QuantizationReport {
+    /// Number of pixels that end up a different colour from the source.
+    /// Without dithering these are the pixels whose colour is not in the
+    /// palette, dithering changes some of the others too.
+    pub changed_pixels: u32,
+    /// Largest CIEDE2000 distance between a source colour and its
+    /// replacement.
+    pub max_error: f64,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default)]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    /// Animated PNG.
+    Apng,
+    WebP,
+}
+
+impl From<AnimationFormat> for pixel_animation::AnimationFormat {
+    fn from(format: AnimationFormat) -> Self {
+        match format {
+            AnimationFormat::Gif => Self::Gif,
+            AnimationFormat::Apng => Self::Apng,
+            AnimationFormat::WebP => Self::WebP,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub enum PaletteFormat {
+    /// GIMP palette, `.gpl`.
+    Gpl,
+    /// Adobe Swatch Exchange, `.ase`, also read by Aseprite.
+    Ase,
+    /// JASC palette, `.pal`.
+    Pal,
+    /// Paint.NET palette, `.txt`.
+    PaintNet,
+    /// 8×8 PNG with one pixel per colour, ordered by code.
+    Png,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct PaletteColor {
+    pub code: u8,
+    /// Hex code without the leading `#`.
+    pub hex: String,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    /// Hue in degrees.
+    pub hue: u16,
+    /// Saturation in percent.
+    pub saturation: u16,
+    /// Lightness in percent.
+    pub lightness: u16,
+    /// Label made of the family and shade, such as `Blue 3`. Palettes have no
+    /// names of their own, so it tells colours of a family apart but does not
+    /// describe them any further.
+    pub name: String,
+    /// Hue family the colour belongs to, such as `blue` or `gray`.
+    pub family: String,
+    /// Position within the family, counting from the darkest colour.
+    pub shade: u8,
+}
+
+/// Bounding box of the pixels changed by a `Canvas` operation.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct DirtyRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Stack of 64×64 layers of palette codes with editing operations. Drawing
+/// operations apply to the active layer and return the rectangle they
+/// changed, or `undefined` if nothing changed. Layers may hold the
+/// `transparent_code()`, which `flatten` resolves before baking. Operations
+/// with codes above it change nothing.
+///
+/// Changes are recorded into an undo step of the active layer that stays
+/// open until `commit`, so that a whole stroke can be undone at once.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Canvas {
+    layers: Vec<canvas::Layer>,
+    active: usize,
+}
+
+/// Most layers a `Canvas` can have.
+pub(crate) const MAX_LAYERS: usize = 16;
+
+/// Block of codes copied out of a `Canvas`.
+#[wasm_bindgen]
+pub struct CanvasRegion(canvas::Region);
+
+#[wasm_bindgen]
+impl CanvasRegion {
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.0.width
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.0.height
+    }
+}
+
+#[wasm_bindgen]
+#[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
+impl Canvas {
+    /// # Errors
+    ///
+    /// Throws if `fill` is above `transparent_code()`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(fill: Option<u8>) -> Result<Self, JsError> {
+        let fill = fill.unwrap_or(0);
+        if !canvas::is_layer_code(fill) {
+            return Err(JsError::new("Invalid fill code"));
+        }
+
+        Ok(Self::from_grid(canvas::Grid::filled(fill)))
+    }
+
+    /// Single layer canvas. Returns `undefined` unless `data` is 64×64 and
+    /// holds palette codes or `transparent_code()`.
+    pub fn from_data(
+        #[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array,
+    ) -> Option<Self> {
+        canvas::Grid::from_data(&data_from_js(&data)).map(Self::from_grid)
+    }
+
+    /// Codes of the active layer.
+    #[wasm_bindgen(unchecked_return_type = "number[][]")]
+    pub fn to_data(&self) -> Array {
+        data_to_js(self.layer().grid.to_data())
+    }
+
+    /// Composites the visible layers, merged bottom to top so that upper
+    /// layers cover lower ones, with `background` showing through where all
+    /// of them are transparent. The result is what
+    /// `encode_dna` and `pack_bake` take. Returns `undefined` unless
+    /// `background` is a palette code.
+    #[wasm_bindgen(unchecked_return_type = "number[][] | undefined")]
+    pub fn flatten(&self, background: Option<u8>) -> Option<Array> {
+        let background = background.unwrap_or(0);
+        if background >= canvas::TRANSPARENT {
+            return None;
+        }
+
+        let mut flattened = canvas::Grid::filled(background);
+
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            flattened.merge(&layer.grid);
+        }
+
+        Some(data_to_js(flattened.to_data()))
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        self.layer().grid.get(x, y)
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, code: u8) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(code) {
+            return None;
+        }
+
+        self.apply(|grid| grid.set(x, y, code))
+    }
+
+    /// Fills the area of the same code around `(x, y)`, through diagonals too
+    /// when `diagonal` is set.
+    pub fn flood_fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        code: u8,
+        diagonal: bool,
+    ) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(code) {
+            return None;
+        }
+
+        self.apply(|grid| grid.flood_fill(x, y, code, diagonal))
+    }
+
+    /// Lines reaching far off the canvas draw nothing.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, code: u8) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(code) {
+            return None;
+        }
+
+        self.apply(|grid| grid.line(x0, y0, x1, y1, code))
+    }
+
+    /// Draws the rectangle with corners `(x0, y0)` and `(x1, y1)`.
+    pub fn rectangle(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        code: u8,
+        filled: bool,
+    ) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(code) {
+            return None;
+        }
+
+        self.apply(|grid| grid.rectangle((x0, y0), (x1, y1), code, filled))
+    }
+
+    /// Draws the ellipse inscribed in the rectangle with corners `(x0, y0)`
+    /// and `(x1, y1)`.
+    pub fn ellipse(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        code: u8,
+        filled: bool,
+    ) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(code) {
+            return None;
+        }
+
+        self.apply(|grid| grid.ellipse((x0, y0), (x1, y1), code, filled))
+    }
+
+    pub fn flip_horizontal(&mut self) -> Option<DirtyRect> {
+        self.apply(canvas::Grid::flip_horizontal)
+    }
+
+    pub fn flip_vertical(&mut self) -> Option<DirtyRect> {
+        self.apply(canvas::Grid::flip_vertical)
+    }
+
+    /// Reflects the left half onto the right half.
+    pub fn mirror_horizontal(&mut self) -> Option<DirtyRect> {
+        self.apply(canvas::Grid::mirror_horizontal)
+    }
+
+    /// Reflects the top half onto the bottom half.
+    pub fn mirror_vertical(&mut self) -> Option<DirtyRect> {
+        self.apply(canvas::Grid::mirror_vertical)
+    }
+
+    /// Rotates by clockwise quarter turns, negative for counter clockwise.
+    pub fn rotate(&mut self, quarter_turns: i32) -> Option<DirtyRect> {
+        self.apply(|grid| grid.rotate(quarter_turns))
+    }
+
+    /// Moves every pixel by `(dx, dy)`, wrapping around the edges.
+    pub fn shift(&mut self, dx: i32, dy: i32) -> Option<DirtyRect> {
+        self.apply(|grid| grid.shift(dx, dy))
+    }
+
+    pub fn replace_color(&mut self, from: u8, to: u8) -> Option<DirtyRect> {
+        if !canvas::is_layer_code(from) || !canvas::is_layer_code(to) {
+            return None;
+        }
+
+        self.apply(|grid| grid.replace_color(from, to))
+    }
+
+    /// Copies the part of the rectangle at `(x, y)` that lies on the canvas.
+    pub fn copy(&self, x: usize, y: usize, width: usize, height: usize) -> CanvasRegion {
+        CanvasRegion(self.layer().grid.copy(x, y, width, height))
+    }
+
+    /// Closes the current undo step.
+    pub fn commit(&mut self) {
+        self.layer_mut().history.commit();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.layer().history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.layer().history.can_redo()
+    }
+
+    /// Reverts the last step of the active layer, committing the current
+    /// one first.
+    pub fn undo(&mut self) -> Option<DirtyRect> {
+        let layer = self.layer_mut();
+        layer.history.undo(&mut layer.grid)
+    }
+
+    pub fn redo(&mut self) -> Option<DirtyRect> {
+        let layer = self.layer_mut();
+        layer.history.redo(&mut layer.grid)
+    }
+
+    /// Serializes the undo and redo stacks of every layer, committing the
+    /// current steps first. Drawings are not included.
+    #[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer>")]
+    pub fn save_history(&mut self) -> Uint8Array {
+        let histories = self.layers.iter_mut().map(|layer| &mut layer.history);
+
+        Uint8Array::new_from_slice(&history::save(histories))
+    }
+
+    /// Restores stacks from `save_history`, meant for the layers they were
+    /// saved with. Returns whether the bytes were valid.
+    pub fn load_history(&mut self, bytes: Uint8Array) -> bool {
+        match history::load(&bytes.to_vec()) {
+            Ok(histories) if histories.len() == self.layers.len() => {
+                for (layer, history) in self.layers.iter_mut().zip(histories) {
+                    layer.history = history;
+                }
+                true
+            }
+            Ok(_) | Err(history::InvalidHistory) => false,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn active_layer(&self) -> usize {
+        self.active
+    }
+
+    /// Layers are numbered from the bottom.
+    #[wasm_bindgen(setter)]
+    pub fn set_active_layer(&mut self, index: usize) {
+        if index < self.layers.len() {
+            self.layer_mut().history.commit();
+            self.active = index;
+        }
+    }
+
+    /// Adds a transparent layer on top and makes it active. Returns its index,
+    /// or `undefined` if there are already as many layers as allowed.
+    pub fn add_layer(&mut self, name: String) -> Option<usize> {
+        if self.layers.len() >= MAX_LAYERS {
+            return None;
+        }
+
+        self.layers.push(canvas::Layer::new(
+            name,
+            canvas::Grid::filled(canvas::TRANSPARENT),
+        ));
+        self.set_active_layer(self.layers.len() - 1);
+        Some(self.active)
+    }
+
+    /// Removes a layer, unless it is the only one.
+    pub fn remove_layer(&mut self, index: usize) -> bool {
+        if index >= self.layers.len() || self.layers.len() == 1 {
+            return false;
+        }
+
+        self.layers.remove(index);
+        if self.active > index || self.active == self.layers.len() {
+            self.active -= 1;
+        }
+        true
+    }
+
+    /// Moves a layer to another position, shifting the ones in between. The
+    /// active layer stays the same.
+    pub fn move_layer(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.layers.len() || to >= self.layers.len() {
+            return false;
+        }
+
+        let active = self.active;
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+
+        self.active = if active == from {
+            to
+        } else if from < active && active <= to {
+            active - 1
+        } else if to <= active && active < from {
+            active + 1
+        } else {
+            active
+        };
+        true
+    }
+
+    /// Draws a layer onto the one below it and removes it. The change to the
+    /// lower layer is its own undo step.
+    pub fn merge_down(&mut self, index: usize) -> bool {
+        if index == 0 || index >= self.layers.len() || self.layers[index - 1].locked {
+            return false;
+        }
+
+        let upper = self.layers.remove(index);
+        let lower = &mut self.layers[index - 1];
+
+        lower.history.commit();
+        let before = lower.grid.clone();
+        if let Some(rect) = lower.grid.merge(&upper.grid) {
+            lower.history.record(&before, &lower.grid, rect);
+            lower.history.commit();
+        }
+
+        if self.active >= index {
+            self.active -= 1;
+        }
+        true
+    }
+
+    pub fn layer_name(&self, index: usize) -> Option<String> {
+        Some(self.layers.get(index)?.name.clone())
+    }
+
+    pub fn set_layer_name(&mut self, index: usize, name: String) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.name = name;
+        }
+    }
+
+    pub fn layer_visible(&self, index: usize) -> Option<bool> {
+        Some(self.layers.get(index)?.visible)
+    }
+
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.visible = visible;
+        }
+    }
+
+    pub fn layer_locked(&self, index: usize) -> Option<bool> {
+        Some(self.layers.get(index)?.locked)
+    }
+
+    pub fn set_layer_locked(&mut self, index: usize, locked: bool) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.locked = locked;
+        }
+    }
+
+    /// Pastes a region with its top left corner at `(x, y)`, skipping pixels
+    /// of the `transparent` code.
+    pub fn paste(
+        &mut self,
+        region: &CanvasRegion,
+        x: i32,
+        y: i32,
+        transparent: Option<u8>,
+    ) -> Option<DirtyRect> {
+        self.apply(|grid| grid.paste(&region.0, x, y, transparent))
+    }
+
+    fn from_grid(grid: canvas::Grid) -> Self {
+        Self {
+            layers: vec![canvas::Layer::new("Background".into(), grid)],
+            active: 0,
+        }
+    }
+
+    fn layer(&self) -> &canvas::Layer {
+        &self.layers[self.active]
+    }
+
+    fn layer_mut(&mut self) -> &mut canvas::Layer {
+        &mut self.layers[self.active]
+    }
+
+    /// Runs an operation on the active layer, unless it is locked, and
+    /// records what it changed.
+    fn apply(
+        &mut self,
+        operation: impl FnOnce(&mut canvas::Grid) -> Option<DirtyRect>,
+    ) -> Option<DirtyRect> {
+        let layer = self.layer_mut();
+        if layer.locked {
+            return None;
+        }
+
+        let before = layer.grid.clone();
+        let rect = operation(&mut layer.grid)?;
+        layer.history.record(&before, &layer.grid, rect);
+        Some(rect)
+    }
 }
 
+/// Unfinished artwork with its layers, in a compact versioned binary form
+/// meant for short links and QR codes.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Draft(draft::Draft);
+
 #[wasm_bindgen]
 #[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
-pub fn parse_image(ext: String, bytes: Uint8Array) -> ParseImageResponse {
+impl Draft {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self(draft::Draft {
+            palette: draft::CANVAS_PALETTE,
+            title: None,
+            artist: None,
+            layers: vec![],
+        })
+    }
+
+    /// Returns `undefined` if the bytes are not a draft this version can
+    /// read.
+    pub fn decode(bytes: Uint8Array) -> Option<Self> {
+        draft::Draft::decode(&bytes.to_vec()).ok().map(Self)
+    }
+
+    /// Returns `undefined` if the title, the artist or a layer name is over
+    /// 65535 bytes long in UTF-8.
+    #[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer> | undefined")]
+    pub fn encode(&self) -> Option<Uint8Array> {
+        self.0
+            .encode()
+            .map(|bytes| Uint8Array::new_from_slice(&bytes))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> Option<String> {
+        self.0.title.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.0.title = title;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn artist(&self) -> Option<String> {
+        self.0.artist.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_artist(&mut self, artist: Option<String>) {
+        self.0.artist = artist;
+    }
+
+    /// Takes the layers of a canvas, without their history.
+    pub fn set_canvas(&mut self, canvas: &Canvas) {
+        self.0.layers = canvas
+            .layers
+            .iter()
+            .map(|layer| draft::Layer {
+                name: layer.name.clone(),
+                visible: layer.visible,
+                locked: layer.locked,
+                grid: layer.grid.clone(),
+            })
+            .collect();
+    }
+
+    /// Canvas with the layers of the draft and the top one active. Returns
+    /// `undefined` if the draft has no layers.
+    pub fn to_canvas(&self) -> Option<Canvas> {
+        if self.0.layers.is_empty() {
+            return None;
+        }
+
+        let layers = self
+            .0
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut canvas_layer = canvas::Layer::new(layer.name.clone(), layer.grid.clone());
+                canvas_layer.visible = layer.visible;
+                canvas_layer.locked = layer.locked;
+                canvas_layer
+            })
+            .collect_vec();
+
+        Some(Canvas {
+            active: layers.len() - 1,
+            layers,
+        })
+    }
+}
+
+impl Default for Draft {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const UNPACK_DNA_RESPONSE_TYPEDEF: &'static str = r#"
+export type UnpackDnaResponse = {
+  status: "ok",
+  data: number[][],
+} | {
+  status: "invalid_boc"
+} | {
+  status: "wrong_bits" | "wrong_refs",
+  path: number[],
+  expected: number,
+  actual: number,
+}"#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const DECODE_DNA_LINK_RESPONSE_TYPEDEF: &'static str = r#"
+export type DecodeDnaLinkResponse = {
+  status: "ok",
+  data: number[][],
+} | {
+  status: "truncated" | "invalid"
+}"#;
+
+#[wasm_bindgen(skip_typescript, getter_with_clone)]
+pub struct DecodeDnaLinkResponse {
+    pub status: String,
+    pub data: JsValue,
+}
+
+#[wasm_bindgen(skip_typescript, getter_with_clone)]
+pub struct UnpackDnaResponse {
+    pub status: String,
+    pub data: JsValue,
+    pub path: JsValue,
+    pub expected: JsValue,
+    pub actual: JsValue,
+}
+
+#[wasm_bindgen]
+#[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
+pub fn parse_image(
+    ext: String,
+    bytes: Uint8Array,
+    options: Option<ParseImageOptions>,
+) -> ParseImageResponse {
     let bytes = bytes.to_vec();
-    parse::parse(&ext, &bytes)
+    parse::parse(&ext, &bytes, options.unwrap_or_default())
 }
 
 #[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer>")]
@@ -51,16 +784,157 @@ pub fn render_image(
     Uint8Array::new_from_slice(&bytes)
 }
 
+/// Renders artworks into a looping animation, one frame each, of at most 32
+/// frames. `delays` are in milliseconds, either one for every frame or one
+/// per frame.
+#[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer> | null")]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn render_animation(
+    #[wasm_bindgen(unchecked_param_type = "number[][][]")] frames: Array,
+    delays: Vec<u16>,
+    format: AnimationFormat,
+    upscale: bool,
+) -> Option<Uint8Array> {
+    if frames.length() == 0 || (delays.len() != 1 && delays.len() != frames.length() as usize) {
+        return None;
+    }
+
+    let frames = frames
+        .iter()
+        .zip(delays.iter().cycle())
+        .map(|(data, delay)| {
+            let codes = data_from_js(&Array::from(&data)).concat();
+            (codes.len() == 64 * 64 && codes.iter().all(|code| *code < 64)).then_some(
+                pixel_animation::Frame {
+                    codes,
+                    delay: *delay,
+                },
+            )
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let bytes = pixel_animation::encode(
+        &frames,
+        if upscale { 10 } else { 1 },
+        format.into(),
+        &palette::COLORS,
+    )?;
+
+    Some(Uint8Array::new_from_slice(&bytes))
+}
+
+/// Largest scale and longest side in pixels `render_text` accepts.
+const MAX_TEXT_SCALE: u32 = 32;
+const MAX_TEXT_SIZE: u32 = 4096;
+
+/// Renders text in the built-in pixel font as a PNG. Returns `null` for
+/// codes outside the palette, a zero scale or text without any characters.
+#[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer> | null")]
+#[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
+pub fn render_text(text: String, code: u8, background: u8, scale: u32) -> Option<Uint8Array> {
+    if code >= 64 || background >= 64 || !(1..=MAX_TEXT_SCALE).contains(&scale) {
+        return None;
+    }
+
+    let (width, height) = pixel_font::measure(&text, scale);
+    if width == 0 || height == 0 || width.max(height) > MAX_TEXT_SIZE {
+        return None;
+    }
+
+    let bytes = render::render_text(&text, code, background, scale);
+
+    Some(Uint8Array::new_from_slice(&bytes))
+}
+
+#[wasm_bindgen(unchecked_return_type = "PaletteColor[]")]
+#[allow(clippy::must_use_candidate)]
+pub fn get_palette() -> Array {
+    palette::ENTRIES
+        .iter()
+        .map(|entry| {
+            let [red, green, blue] = entry.rgb;
+            let [hue, saturation, lightness] = entry.hsl;
+
+            JsValue::from(PaletteColor {
+                code: entry.code,
+                hex: format!("{red:02X}{green:02X}{blue:02X}"),
+                red,
+                green,
+                blue,
+                hue,
+                saturation,
+                lightness,
+                name: entry.name.into(),
+                family: entry.family.into(),
+                shade: entry.shade,
+            })
+        })
+        .collect()
+}
+
+#[wasm_bindgen(unchecked_return_type = "Uint8Array<ArrayBuffer>")]
+#[allow(clippy::must_use_candidate, clippy::missing_panics_doc)]
+pub fn export_palette(format: PaletteFormat) -> Uint8Array {
+    let bytes = palette_file::export(format);
+
+    Uint8Array::new_from_slice(&bytes)
+}
+
 #[wasm_bindgen(unchecked_return_type = "number[][] | null")]
 #[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
 pub fn decode_dna(dna: String) -> Option<Array> {
-    Some(
-        Dna::from_base64(&dna)?
-            .to_data()
-            .into_iter()
-            .map(|row| row.into_iter().map(Number::from).collect::<Array>())
-            .collect::<Array>(),
-    )
+    Some(data_to_js(Dna::from_base64(&dna)?.to_data()))
+}
+
+#[wasm_bindgen(unchecked_return_type = "UnpackDnaResponse")]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn unpack_dna(boc: String) -> UnpackDnaResponse {
+    let mut response = UnpackDnaResponse {
+        status: String::new(),
+        data: JsValue::UNDEFINED,
+        path: JsValue::UNDEFINED,
+        expected: JsValue::UNDEFINED,
+        actual: JsValue::UNDEFINED,
+    };
+
+    let (status, path, expected, actual) = match Dna::from_boc(&boc) {
+        Ok(dna) => {
+            response.status = "ok".into();
+            response.data = data_to_js(dna.to_data()).into();
+            return response;
+        }
+        Err(DnaCellError::InvalidBoc) => {
+            response.status = "invalid_boc".into();
+            return response;
+        }
+        Err(DnaCellError::WrongBits {
+            path,
+            expected,
+            actual,
+        }) => ("wrong_bits", path, expected, actual),
+        Err(DnaCellError::WrongRefs {
+            path,
+            expected,
+            actual,
+        }) => ("wrong_refs", path, expected, actual),
+    };
+
+    response.status = status.into();
+    response.path = path
+        .into_iter()
+        .map(|index| Number::from(u32::try_from(index).unwrap()))
+        .collect::<Array>()
+        .into();
+    response.expected = Number::from(u32::try_from(expected).unwrap()).into();
+    response.actual = Number::from(u32::try_from(actual).unwrap()).into();
+    response
 }
 
 #[wasm_bindgen]
@@ -72,7 +946,79 @@ pub fn decode_dna(dna: String) -> Option<Array> {
 pub fn encode_dna(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array) -> String {
     let data = data_from_js(&data);
 
-    Dna::from_data(data).to_base64()
+    Dna::from_data(&data).to_base64()
+}
+
+/// Longest start parameter Telegram passes to bots and mini apps.
+const TELEGRAM_START_PARAM_MAX_LENGTH: usize = 512;
+
+/// Encodes artwork for sharing in links. The result is at most
+/// `dna_link_max_length()` characters long, more than Telegram allows in
+/// start parameters, which `encode_dna_start_param` is for.
+#[wasm_bindgen]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn encode_dna_link(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array) -> String {
+    let data = data_from_js(&data);
+
+    Dna::from_data(&data).to_link()
+}
+
+/// Encodes artwork for sharing as a Telegram start parameter, the same way as
+/// `encode_dna_link`. Returns `undefined` when the link is longer than
+/// Telegram allows, as detailed artwork doesn't compress enough to fit and
+/// has to be shared some other way.
+#[wasm_bindgen]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn encode_dna_start_param(
+    #[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array,
+) -> Option<String> {
+    let data = data_from_js(&data);
+
+    let link = Dna::from_data(&data).to_link();
+    (link.len() <= TELEGRAM_START_PARAM_MAX_LENGTH).then_some(link)
+}
+
+/// Code of transparent pixels in `Canvas` layers.
+#[wasm_bindgen]
+#[allow(clippy::must_use_candidate)]
+pub fn transparent_code() -> u8 {
+    canvas::TRANSPARENT
+}
+
+#[wasm_bindgen]
+#[allow(clippy::must_use_candidate)]
+pub fn dna_link_max_length() -> usize {
+    canvas_dna::LINK_MAX_LENGTH
+}
+
+/// Decodes a link from `encode_dna_link` or `encode_dna_start_param`. Links
+/// cut short come back as `truncated` rather than `invalid`, so that users
+/// can be told to copy them whole; the artwork itself is lost either way.
+#[wasm_bindgen(unchecked_return_type = "DecodeDnaLinkResponse")]
+#[allow(clippy::must_use_candidate, clippy::needless_pass_by_value)]
+pub fn decode_dna_link(link: String) -> DecodeDnaLinkResponse {
+    match Dna::from_link(&link) {
+        Ok(dna) => DecodeDnaLinkResponse {
+            status: "ok".into(),
+            data: data_to_js(dna.to_data()).into(),
+        },
+        Err(err) => DecodeDnaLinkResponse {
+            status: match err {
+                DnaLinkError::Truncated => "truncated",
+                DnaLinkError::Invalid => "invalid",
+            }
+            .into(),
+            data: JsValue::UNDEFINED,
+        },
+    }
 }
 
 #[wasm_bindgen]
@@ -80,7 +1026,7 @@ pub fn encode_dna(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Arr
 pub fn pack_dna(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array) -> String {
     let data = data_from_js(&data);
 
-    let dna = Dna::from_data(data);
+    let dna = Dna::from_data(&data);
 
     pack::pack_dna(&dna)
 }
@@ -94,17 +1040,53 @@ pub fn pack_bake(
 ) -> String {
     let data = data_from_js(&data);
 
-    let dna = Dna::from_data(data);
+    let dna = Dna::from_data(&data);
 
     pack::pack_bake(&title, &artist, &dna)
 }
 
+#[wasm_bindgen]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn dna_hash(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array) -> BigInt {
+    let data = data_from_js(&data);
+
+    bigint_from_bytes(&Dna::from_data(&data).hash())
+}
+
+#[wasm_bindgen]
+#[allow(
+    clippy::must_use_candidate,
+    clippy::needless_pass_by_value,
+    clippy::missing_panics_doc
+)]
+pub fn dna_mint_id(#[wasm_bindgen(unchecked_param_type = "number[][]")] data: Array) -> BigInt {
+    let data = data_from_js(&data);
+
+    bigint_from_bytes(&Dna::from_data(&data).mint_id())
+}
+
 #[wasm_bindgen]
 #[allow(clippy::must_use_candidate)]
 pub fn pack_purchase_exclusive(item_index: u32) -> String {
     pack::pack_purchase_exclusive(item_index)
 }
 
+fn bigint_from_bytes(bytes: &[u8]) -> BigInt {
+    let hex = bytes.iter().map(|byte| format!("{byte:02x}")).join("");
+
+    BigInt::new(&JsValue::from_str(&format!("0x{hex}"))).unwrap()
+}
+
+fn data_to_js(data: Vec<Vec<u8>>) -> Array {
+    data.into_iter()
+        .map(|row| row.into_iter().map(Number::from).collect::<Array>())
+        .collect::<Array>()
+}
+
 fn data_from_js(js: &Array) -> Vec<Vec<u8>> {
     js.to_vec()
         .into_iter()
@@ -1,6 +1,6 @@
 use tonlib_core::{TonAddress, cell::CellBuilder, tlb_types::tlb::TLB};
 
-use crate::dna::Dna;
+use canvas_dna::Dna;
 
 pub fn pack_dna(dna: &Dna) -> String {
     dna.to_cell().to_boc_b64(true).unwrap()
@@ -0,0 +1,41 @@
+use crate::color::Lab;
+use std::sync::LazyLock;
+
+pub static COLORS: phf::Map<u8, image::Rgb<u8>> = rust_colors::colors!(canvas);
+
+pub static CODES: phf::Map<[u8; 3], u8> = rust_colors::codes!(canvas);
+
+pub static ENTRIES: [PaletteEntry; 64] = rust_colors::entries!(canvas);
+
+pub struct PaletteEntry {
+    pub code: u8,
+    pub rgb: [u8; 3],
+    pub hsl: [u16; 3],
+    pub name: &'static str,
+    pub family: &'static str,
+    /// Position within the family, counting from the darkest colour.
+    pub shade: u8,
+}
+
+static LAB_COLORS: LazyLock<Vec<(u8, Lab)>> = LazyLock::new(|| {
+    COLORS
+        .entries()
+        .map(|(code, color)| (*code, Lab::from_rgb(color.0)))
+        .collect()
+});
+
+/// Finds the perceptually closest palette colour, returning its code and the
+/// CIEDE2000 distance to it.
+pub fn nearest(rgb: [u8; 3]) -> (u8, f64) {
+    if let Some(code) = CODES.get(&rgb) {
+        return (*code, 0.0);
+    }
+
+    let lab = Lab::from_rgb(rgb);
+
+    LAB_COLORS
+        .iter()
+        .map(|(code, color)| (*code, lab.delta_e(*color)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
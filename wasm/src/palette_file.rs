@@ -0,0 +1,105 @@
+use crate::{PaletteFormat, palette::ENTRIES};
+use image::{Rgb, RgbImage, codecs::png::PngEncoder};
+use std::fmt::Write;
+
+const NAME: &str = "Canvas";
+
+/// Serializes the palette into a file that external editors can load,
+/// with colours ordered by their code.
+pub fn export(format: PaletteFormat) -> Vec<u8> {
+    match format {
+        PaletteFormat::Gpl => gpl().into_bytes(),
+        PaletteFormat::Ase => ase(),
+        PaletteFormat::Pal => pal().into_bytes(),
+        PaletteFormat::PaintNet => paint_net().into_bytes(),
+        PaletteFormat::Png => png(),
+    }
+}
+
+fn colors() -> impl Iterator<Item = (u8, [u8; 3])> {
+    ENTRIES.iter().map(|entry| (entry.code, entry.rgb))
+}
+
+fn color_name(code: u8) -> &'static str {
+    ENTRIES[usize::from(code)].name
+}
+
+fn gpl() -> String {
+    let mut file = format!("GIMP Palette\nName: {NAME}\nColumns: 8\n#\n");
+
+    for (code, [r, g, b]) in colors() {
+        writeln!(file, "{r:3} {g:3} {b:3}\t{}", color_name(code)).unwrap();
+    }
+
+    file
+}
+
+/// Adobe Swatch Exchange, which Aseprite also reads. All numbers are big
+/// endian and names are null-terminated UTF-16.
+fn ase() -> Vec<u8> {
+    const COLOR_ENTRY: u16 = 0x0001;
+    const NORMAL_COLOR: u16 = 2;
+
+    let mut file = b"ASEF".to_vec();
+    file.extend(1u16.to_be_bytes());
+    file.extend(0u16.to_be_bytes());
+    file.extend(64u32.to_be_bytes());
+
+    for (code, rgb) in colors() {
+        let name = color_name(code)
+            .encode_utf16()
+            .chain([0])
+            .collect::<Vec<_>>();
+
+        let mut block = u16::try_from(name.len()).unwrap().to_be_bytes().to_vec();
+        block.extend(name.into_iter().flat_map(u16::to_be_bytes));
+        block.extend(b"RGB ");
+        block.extend(
+            rgb.into_iter()
+                .flat_map(|channel| (f32::from(channel) / 255.0).to_be_bytes()),
+        );
+        block.extend(NORMAL_COLOR.to_be_bytes());
+
+        file.extend(COLOR_ENTRY.to_be_bytes());
+        file.extend(u32::try_from(block.len()).unwrap().to_be_bytes());
+        file.extend(block);
+    }
+
+    file
+}
+
+fn pal() -> String {
+    let mut file = "JASC-PAL\r\n0100\r\n64\r\n".to_owned();
+
+    for (_, [r, g, b]) in colors() {
+        write!(file, "{r} {g} {b}\r\n").unwrap();
+    }
+
+    file
+}
+
+fn paint_net() -> String {
+    let mut file = format!(";paint.net Palette File\n;Palette Name: {NAME}\n;Colors: 64\n");
+
+    for (_, [r, g, b]) in colors() {
+        writeln!(file, "FF{r:02X}{g:02X}{b:02X}").unwrap();
+    }
+
+    file
+}
+
+/// One pixel per colour, row by row, so that code `n` sits at `(n % 8, n / 8)`.
+fn png() -> Vec<u8> {
+    let mut image = RgbImage::new(8, 8);
+
+    for (code, rgb) in colors() {
+        image.put_pixel(u32::from(code % 8), u32::from(code / 8), Rgb(rgb));
+    }
+
+    let mut bytes = vec![];
+    image
+        .write_with_encoder(PngEncoder::new(&mut bytes))
+        .unwrap();
+
+    bytes
+}
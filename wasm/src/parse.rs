@@ -1,20 +1,61 @@
-use crate::ParseImageResponse;
+use crate::{
+    Dithering, ParseImageOptions, ParseImageResponse, QuantizationReport, Resize,
+    WrongPaletteColor, palette, project, quantize, resize,
+};
 use image::{
-    DynamicImage, ImageDecoder, ImageError,
-    codecs::{bmp::BmpDecoder, jpeg::JpegDecoder, png::PngDecoder},
+    DynamicImage, ImageDecoder, ImageError, Rgb, RgbImage, RgbaImage,
+    codecs::{
+        bmp::BmpDecoder, gif::GifDecoder, ico::IcoDecoder, jpeg::JpegDecoder, png::PngDecoder,
+        qoi::QoiDecoder, tga::TgaDecoder, webp::WebPDecoder,
+    },
 };
 use itertools::Itertools;
 use js_sys::{Array, Number};
-use std::{io::Cursor, str::FromStr};
+use std::{collections::HashMap, io::Cursor};
 use wasm_bindgen::JsValue;
 
-pub fn parse(ext: &str, bytes: &[u8]) -> ParseImageResponse {
-    let result = try_parse(ext, bytes);
+/// Longest side of images that are decoded, as every pixel is held in
+/// memory before resizing.
+const MAX_SIDE: u32 = 4096;
+
+pub fn parse(ext: &str, bytes: &[u8], options: ParseImageOptions) -> ParseImageResponse {
+    let mut diagnostics = Diagnostics::default();
+    let result = try_parse(ext, bytes, options, &mut diagnostics);
+
+    let unmappable_colors = diagnostics
+        .unmappable_colors
+        .map_or(JsValue::UNDEFINED, |colors| {
+            colors
+                .into_iter()
+                .map(JsValue::from)
+                .collect::<Array>()
+                .into()
+        });
+
+    let wrong_colors = diagnostics
+        .wrong_colors
+        .map_or(JsValue::UNDEFINED, |colors| {
+            colors
+                .into_iter()
+                .map(|color| {
+                    JsValue::from(WrongPaletteColor {
+                        color: hex(color.rgb),
+                        count: color.count,
+                        x: color.x,
+                        y: color.y,
+                        nearest: color.nearest,
+                        distance: color.distance,
+                    })
+                })
+                .collect::<Array>()
+                .into()
+        });
 
     match result {
-        Ok(image) => ParseImageResponse {
+        Ok(parsed) => ParseImageResponse {
             status: "ok".into(),
-            data: image
+            data: parsed
+                .data
                 .into_iter()
                 .map(|row| {
                     row.into_iter()
@@ -23,6 +64,10 @@ pub fn parse(ext: &str, bytes: &[u8]) -> ParseImageResponse {
                 })
                 .collect::<Array>()
                 .into(),
+            quantization: parsed.quantization,
+            background_pixels: Some(parsed.background_pixels),
+            unmappable_colors,
+            wrong_colors,
         },
         Err(err) => ParseImageResponse {
             status: match err {
@@ -30,45 +75,167 @@ pub fn parse(ext: &str, bytes: &[u8]) -> ParseImageResponse {
                 ParseImageError::DecodeError => "decode_error",
                 ParseImageError::WrongDimensions => "wrong_dimensions",
                 ParseImageError::WrongPalette => "wrong_palette",
+                ParseImageError::InvalidBackground => "invalid_background",
             }
             .into(),
             data: JsValue::UNDEFINED,
+            quantization: None,
+            background_pixels: None,
+            unmappable_colors,
+            wrong_colors,
         },
     }
 }
 
-static CODES: phf::Map<[u8; 3], u8> = rust_colors::codes!();
+struct Parsed {
+    data: Vec<Vec<u8>>,
+    quantization: Option<QuantizationReport>,
+    background_pixels: u32,
+}
+
+/// Details about the source image that are reported regardless of whether
+/// parsing succeeds.
+#[derive(Default)]
+struct Diagnostics {
+    unmappable_colors: Option<Vec<String>>,
+    wrong_colors: Option<Vec<WrongColor>>,
+}
+
+struct WrongColor {
+    rgb: [u8; 3],
+    count: u32,
+    x: u32,
+    y: u32,
+    nearest: u8,
+    distance: f64,
+}
+
+fn try_parse(
+    ext: &str,
+    bytes: &[u8],
+    options: ParseImageOptions,
+    diagnostics: &mut Diagnostics,
+) -> Result<Parsed, ParseImageError> {
+    let format = ImageFormat::detect(ext, bytes)?;
 
-fn try_parse(ext: &str, bytes: &[u8]) -> Result<Vec<Vec<u8>>, ParseImageError> {
-    let ext = ImageExtension::from_str(ext)?;
+    let Some(background) = palette::COLORS.get(&options.background).copied() else {
+        return Err(ParseImageError::InvalidBackground);
+    };
 
-    let decoder = ext
-        .decoder(bytes)
+    let image = if let ImageFormat::Aseprite | ImageFormat::Piskel = format {
+        let project = if let ImageFormat::Aseprite = format {
+            project::decode_aseprite(bytes)
+        } else {
+            project::decode_piskel(bytes)
+        }
         .map_err(|_| ParseImageError::DecodeError)?;
 
-    let dimensions = decoder.dimensions();
-    if dimensions.0 != 64 || dimensions.1 != 64 {
-        return Err(ParseImageError::WrongDimensions);
+        diagnostics.unmappable_colors = Some(
+            project
+                .palette
+                .iter()
+                .filter(|color| !palette::CODES.contains_key(*color))
+                .unique()
+                .map(|color| hex(*color))
+                .collect(),
+        );
+
+        project.image
+    } else {
+        let decoder = format
+            .decoder(bytes)
+            .map_err(|_| ParseImageError::DecodeError)?;
+
+        let dimensions = decoder.dimensions();
+        if dimensions.0 > MAX_SIDE || dimensions.1 > MAX_SIDE {
+            return Err(ParseImageError::WrongDimensions);
+        }
+        if let Resize::None = options.resize
+            && (dimensions.0 != dimensions.1 || dimensions.0 == 0 || dimensions.0 % 64 != 0)
+        {
+            return Err(ParseImageError::WrongDimensions);
+        }
+
+        DynamicImage::from_decoder(decoder)
+            .map_err(|_| ParseImageError::DecodeError)?
+            .to_rgba8()
+    };
+
+    let (image, background_pixels) = flatten(&image, background, options.alpha_threshold);
+
+    if image.width() == 0 || image.height() == 0 {
+        return Err(ParseImageError::DecodeError);
     }
 
-    let image = DynamicImage::from_decoder(decoder)
-        .map_err(|_| ParseImageError::DecodeError)?
-        .to_rgb8();
+    let image = resize::resize(&image, options.resize, background)
+        .ok_or(ParseImageError::WrongDimensions)?;
 
-    let mut parsed = vec![];
+    if options.quantize || !matches!(options.dithering, Dithering::None) {
+        let (data, report) = quantize::quantize(&image, options.dithering);
 
-    for row in &image.pixels().chunks(64) {
-        let mut parsed_row = vec![];
-        for pixel in row {
-            let Some(code) = CODES.get(&[pixel.0[0], pixel.0[1], pixel.0[2]]).copied() else {
-                return Err(ParseImageError::WrongPalette);
-            };
-            parsed_row.push(code);
+        return Ok(Parsed {
+            data,
+            quantization: Some(report),
+            background_pixels,
+        });
+    }
+
+    let mut parsed = vec![vec![0; 64]; 64];
+    let mut wrong_colors = Vec::<WrongColor>::new();
+    // Positions in `wrong_colors`, which keeps the order of appearance.
+    let mut wrong_indices = HashMap::<[u8; 3], usize>::new();
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if let Some(code) = palette::CODES.get(&pixel.0) {
+            parsed[y as usize][x as usize] = *code;
+        } else if let Some(index) = wrong_indices.get(&pixel.0) {
+            wrong_colors[*index].count += 1;
+        } else {
+            wrong_indices.insert(pixel.0, wrong_colors.len());
+            let (nearest, distance) = palette::nearest(pixel.0);
+            wrong_colors.push(WrongColor {
+                rgb: pixel.0,
+                count: 1,
+                x,
+                y,
+                nearest,
+                distance,
+            });
         }
-        parsed.push(parsed_row);
     }
 
-    Ok(parsed)
+    if !wrong_colors.is_empty() {
+        diagnostics.wrong_colors = Some(wrong_colors);
+        return Err(ParseImageError::WrongPalette);
+    }
+
+    Ok(Parsed {
+        data: parsed,
+        quantization: None,
+        background_pixels,
+    })
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("{r:02X}{g:02X}{b:02X}")
+}
+
+/// Drops the alpha channel, replacing pixels at or below `alpha_threshold`
+/// with `background` and counting them.
+fn flatten(image: &RgbaImage, background: Rgb<u8>, alpha_threshold: u8) -> (RgbImage, u32) {
+    let mut background_pixels = 0;
+
+    let flattened = RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        if a <= alpha_threshold {
+            background_pixels += 1;
+            background
+        } else {
+            Rgb([r, g, b])
+        }
+    });
+
+    (flattened, background_pixels)
 }
 
 enum ParseImageError {
@@ -76,33 +243,195 @@ enum ParseImageError {
     DecodeError,
     WrongDimensions,
     WrongPalette,
+    InvalidBackground,
 }
 
-enum ImageExtension {
+enum ImageFormat {
     Png,
     Jpeg,
     Bmp,
+    Gif,
+    WebP,
+    Tga,
+    Ico,
+    Qoi,
+    Aseprite,
+    Piskel,
 }
 
-impl ImageExtension {
+impl ImageFormat {
+    /// Detects the format by its magic bytes, falling back to the extension
+    /// only for formats without a signature.
+    fn detect(ext: &str, bytes: &[u8]) -> Result<Self, ParseImageError> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Ok(Self::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Ok(Self::Jpeg)
+        } else if bytes.starts_with(b"BM") {
+            Ok(Self::Bmp)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Ok(Self::Gif)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            Ok(Self::WebP)
+        } else if bytes.starts_with(&[0, 0, 1, 0]) {
+            Ok(Self::Ico)
+        } else if bytes.starts_with(b"qoif") {
+            Ok(Self::Qoi)
+        } else if project::is_aseprite(bytes) {
+            Ok(Self::Aseprite)
+        } else if ext.eq_ignore_ascii_case("tga") {
+            Ok(Self::Tga)
+        } else if project::is_piskel(ext, bytes) {
+            Ok(Self::Piskel)
+        } else {
+            Err(ParseImageError::UnsupportedExtension)
+        }
+    }
+
     fn decoder<'a>(&self, bytes: &'a [u8]) -> Result<Box<dyn ImageDecoder + 'a>, ImageError> {
         let cursor = Cursor::new(bytes);
         Ok(match self {
             Self::Png => Box::new(PngDecoder::new(cursor)?),
             Self::Jpeg => Box::new(JpegDecoder::new(cursor)?),
             Self::Bmp => Box::new(BmpDecoder::new(cursor)?),
+            // Only the first frame of an animation is decoded.
+            Self::Gif => Box::new(GifDecoder::new(cursor)?),
+            Self::WebP => Box::new(WebPDecoder::new(cursor)?),
+            Self::Tga => Box::new(TgaDecoder::new(cursor)?),
+            Self::Ico => Box::new(IcoDecoder::new(cursor)?),
+            Self::Qoi => Box::new(QoiDecoder::new(cursor)?),
+            Self::Aseprite | Self::Piskel => {
+                unreachable!("project files are not decoded as images")
+            }
         })
     }
 }
 
-impl FromStr for ImageExtension {
-    type Err = ParseImageError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "png" => Ok(Self::Png),
-            "jpg" | "jpeg" => Ok(Self::Jpeg),
-            "bmp" => Ok(Self::Bmp),
-            _ => Err(ParseImageError::UnsupportedExtension),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render;
+    use image::{ImageFormat as Encoding, codecs::png::PngEncoder};
+
+    /// Artwork using every palette code.
+    fn data() -> Vec<Vec<u8>> {
+        (0..64)
+            .map(|y| {
+                (0..64)
+                    .map(|x| u8::try_from((x + y * 3) % 64).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn parse(ext: &str, bytes: &[u8]) -> Result<Parsed, ParseImageError> {
+        try_parse(
+            ext,
+            bytes,
+            ParseImageOptions::default(),
+            &mut Diagnostics::default(),
+        )
+    }
+
+    #[test]
+    fn upscaled_renders_collapse_to_the_original() {
+        let bytes = render::render(&data(), true);
+
+        let Ok(parsed) = parse("png", &bytes) else {
+            panic!("render does not parse");
+        };
+        assert_eq!(parsed.data, data());
+    }
+
+    #[test]
+    fn uneven_upscales_are_rejected() {
+        let bytes = render::render(&data(), true);
+        let mut image = image::load_from_memory_with_format(&bytes, Encoding::Png)
+            .unwrap()
+            .to_rgb8();
+        let other = *image.get_pixel(10, 0);
+        image.put_pixel(5, 5, other);
+
+        let mut bytes = vec![];
+        image
+            .write_with_encoder(PngEncoder::new(&mut bytes))
+            .unwrap();
+
+        assert!(matches!(
+            parse("png", &bytes),
+            Err(ParseImageError::WrongDimensions)
+        ));
+    }
+
+    #[test]
+    fn formats_are_sniffed_from_content() {
+        // Icons hold RGBA images, which every other encoder takes too.
+        let data = data();
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            let [r, g, b] = palette::COLORS[&data[y as usize][x as usize]].0;
+            image::Rgba([r, g, b, 255])
+        });
+
+        for (encoding, ext) in [
+            (Encoding::Png, "jpg"),
+            (Encoding::Bmp, "png"),
+            (Encoding::Gif, "png"),
+            (Encoding::WebP, "png"),
+            (Encoding::Ico, "png"),
+            (Encoding::Qoi, "png"),
+            // TGA has no signature, so only the extension tells it.
+            (Encoding::Tga, "TGA"),
+        ] {
+            let mut bytes = Cursor::new(vec![]);
+            image.write_to(&mut bytes, encoding).unwrap();
+
+            let Ok(parsed) = parse(ext, bytes.get_ref()) else {
+                panic!("{encoding:?} does not parse");
+            };
+            assert_eq!(parsed.data, data, "{encoding:?}");
         }
     }
+
+    #[test]
+    fn unknown_content_is_unsupported() {
+        assert!(matches!(
+            parse("png", b"not an image"),
+            Err(ParseImageError::UnsupportedExtension)
+        ));
+        assert!(matches!(
+            parse("png", b"\x89PNG\r\n\x1a\n"),
+            Err(ParseImageError::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn wrong_colours_are_counted_in_order_of_appearance() {
+        // Black is in the palette, the others are not.
+        let mut image = RgbaImage::from_pixel(64, 64, image::Rgba([0, 0, 0, 255]));
+        image.put_pixel(5, 0, image::Rgba([1, 2, 3, 255]));
+        image.put_pixel(2, 1, image::Rgba([4, 5, 6, 255]));
+        image.put_pixel(9, 3, image::Rgba([1, 2, 3, 255]));
+
+        let mut bytes = vec![];
+        image
+            .write_with_encoder(PngEncoder::new(&mut bytes))
+            .unwrap();
+
+        let mut diagnostics = Diagnostics::default();
+        let result = try_parse(
+            "png",
+            &bytes,
+            ParseImageOptions::default(),
+            &mut diagnostics,
+        );
+        assert!(matches!(result, Err(ParseImageError::WrongPalette)));
+
+        let wrong_colors = diagnostics
+            .wrong_colors
+            .unwrap()
+            .into_iter()
+            .map(|color| (color.rgb, color.count, color.x, color.y))
+            .collect::<Vec<_>>();
+        assert_eq!(wrong_colors, [([1, 2, 3], 2, 5, 0), ([4, 5, 6], 1, 2, 1)]);
+    }
 }
@@ -0,0 +1,465 @@
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use image::{ImageFormat, Rgba, RgbaImage, imageops};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::io::Read;
+
+/// First frame of a project file flattened into a single image, along with
+/// the colours its palette defines.
+pub struct Project {
+    pub image: RgbaImage,
+    pub palette: Vec<[u8; 3]>,
+}
+
+pub struct InvalidProject;
+
+/// Longest side of the canvases and cels that are read, which keeps
+/// allocations for them bounded.
+const MAX_SIDE: u32 = 4096;
+
+/// Most pixel bytes decoded across the cels of a frame, the size of a single
+/// cel as large as allowed.
+const MAX_PIXEL_BYTES: usize = MAX_SIDE as usize * MAX_SIDE as usize * 4;
+
+/// Most colours a palette holds, as cels index it with a byte.
+const MAX_PALETTE_SIZE: usize = 256;
+
+const ASEPRITE_MAGIC: u16 = 0xa5e0;
+const ASEPRITE_FRAME_MAGIC: u16 = 0xf1fa;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_BACKGROUND: u16 = 8;
+const LAYER_GROUP: u16 = 1;
+
+const CEL_RAW: u16 = 0;
+const CEL_COMPRESSED: u16 = 2;
+
+pub fn is_aseprite(bytes: &[u8]) -> bool {
+    bytes.get(4..6) == Some(&ASEPRITE_MAGIC.to_le_bytes())
+}
+
+/// Reads the first frame of an `.aseprite`/`.ase` file, compositing its
+/// visible layers bottom to top. Opacity and blend modes are ignored, as any
+/// pixel that is not fully transparent replaces what is below it.
+pub fn decode_aseprite(bytes: &[u8]) -> Result<Project, InvalidProject> {
+    let mut reader = Reader(bytes);
+
+    let header = reader.take(128)?;
+    let mut header = Reader(header);
+    header.skip(4)?;
+    if header.u16()? != ASEPRITE_MAGIC {
+        return Err(InvalidProject);
+    }
+    header.skip(2)?;
+    let width = u32::from(header.u16()?);
+    let height = u32::from(header.u16()?);
+    let depth = header.u16()?;
+    header.skip(14)?;
+    let transparent_index = header.u8()?;
+
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(InvalidProject);
+    }
+
+    let bytes_per_pixel = match depth {
+        32 => 4,
+        16 => 2,
+        8 => 1,
+        _ => return Err(InvalidProject),
+    };
+
+    let frame_size = reader.u32()? as usize;
+    let mut frame = Reader(reader.take(frame_size.checked_sub(4).ok_or(InvalidProject)?)?);
+    if frame.u16()? != ASEPRITE_FRAME_MAGIC {
+        return Err(InvalidProject);
+    }
+    let old_chunks = frame.u16()?;
+    frame.skip(4)?;
+    let num_chunks = match frame.u32()? {
+        0 => u32::from(old_chunks),
+        n => n,
+    };
+
+    let Frame {
+        layers,
+        cels,
+        palette,
+    } = read_chunks(&mut frame, num_chunks, bytes_per_pixel)?;
+
+    let mut image = RgbaImage::new(width, height);
+
+    for cel in cels.iter().sorted_by_key(|cel| {
+        (
+            cel.layer.cast_signed() + isize::from(cel.z_index),
+            cel.z_index,
+        )
+    }) {
+        let Some(&(is_visible, is_background)) = layers.get(cel.layer) else {
+            return Err(InvalidProject);
+        };
+
+        if !is_visible {
+            continue;
+        }
+
+        if cel.pixels.len() < (cel.width * cel.height) as usize * bytes_per_pixel {
+            return Err(InvalidProject);
+        }
+
+        for (offset, pixel) in cel.pixels.chunks_exact(bytes_per_pixel).enumerate() {
+            let offset = u32::try_from(offset).map_err(|_| InvalidProject)?;
+            if offset >= cel.width * cel.height {
+                break;
+            }
+
+            let color = match *pixel {
+                [red, green, blue, alpha] => Rgba([red, green, blue, alpha]),
+                [value, alpha] => Rgba([value, value, value, alpha]),
+                [index] if index == transparent_index && !is_background => Rgba([0; 4]),
+                [index] => {
+                    let [red, green, blue] =
+                        *palette.get(usize::from(index)).ok_or(InvalidProject)?;
+                    Rgba([red, green, blue, 255])
+                }
+                _ => unreachable!(),
+            };
+
+            if color.0[3] == 0 {
+                continue;
+            }
+
+            let x = i64::from(cel.x) + i64::from(offset % cel.width);
+            let y = i64::from(cel.y) + i64::from(offset / cel.width);
+
+            if let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y))
+                && x < width
+                && y < height
+            {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    Ok(Project { image, palette })
+}
+
+struct Frame {
+    /// Visibility, including that of parent groups, and whether each layer is
+    /// a background layer.
+    layers: Vec<(bool, bool)>,
+    cels: Vec<Cel>,
+    palette: Vec<[u8; 3]>,
+}
+
+struct Cel {
+    layer: usize,
+    z_index: i16,
+    x: i16,
+    y: i16,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn read_chunks(
+    frame: &mut Reader,
+    num_chunks: u32,
+    bytes_per_pixel: usize,
+) -> Result<Frame, InvalidProject> {
+    let mut layers = vec![];
+    let mut group_visibility = vec![];
+    let mut cels = vec![];
+    let mut pixel_bytes = 0;
+    let mut palette = vec![];
+    let mut old_palette = vec![];
+
+    for _ in 0..num_chunks {
+        let chunk_size = frame.u32()? as usize;
+        let chunk_type = frame.u16()?;
+        let mut chunk = Reader(frame.take(chunk_size.checked_sub(6).ok_or(InvalidProject)?)?);
+
+        match chunk_type {
+            CHUNK_LAYER => {
+                let flags = chunk.u16()?;
+                let layer_type = chunk.u16()?;
+                let level = usize::from(chunk.u16()?);
+
+                group_visibility.truncate(level);
+                let is_parent_visible = group_visibility.last().copied().unwrap_or(true);
+                let is_visible = is_parent_visible && flags & LAYER_VISIBLE != 0;
+
+                if layer_type == LAYER_GROUP {
+                    group_visibility.resize(level, is_parent_visible);
+                    group_visibility.push(is_visible);
+                }
+
+                layers.push((is_visible, flags & LAYER_BACKGROUND != 0));
+            }
+            CHUNK_CEL => {
+                if let Some(cel) = read_cel(&mut chunk, bytes_per_pixel)? {
+                    pixel_bytes += cel.pixels.len();
+                    if pixel_bytes > MAX_PIXEL_BYTES {
+                        return Err(InvalidProject);
+                    }
+                    cels.push(cel);
+                }
+            }
+            CHUNK_PALETTE => {
+                let size = chunk.u32()? as usize;
+                let first = chunk.u32()? as usize;
+                let last = chunk.u32()? as usize;
+                chunk.skip(8)?;
+
+                if size > MAX_PALETTE_SIZE || first > last || last >= size {
+                    return Err(InvalidProject);
+                }
+
+                palette.resize(size.max(palette.len()), [0; 3]);
+
+                for color in &mut palette[first..=last] {
+                    let flags = chunk.u16()?;
+                    let rgba = chunk.take(4)?;
+                    if flags & 1 != 0 {
+                        let name_length = usize::from(chunk.u16()?);
+                        chunk.skip(name_length)?;
+                    }
+
+                    *color = [rgba[0], rgba[1], rgba[2]];
+                }
+            }
+            CHUNK_OLD_PALETTE => {
+                let mut index = 0;
+                for _ in 0..chunk.u16()? {
+                    index += usize::from(chunk.u8()?);
+                    let count = match chunk.u8()? {
+                        0 => 256,
+                        n => usize::from(n),
+                    };
+
+                    if index + count > MAX_PALETTE_SIZE {
+                        return Err(InvalidProject);
+                    }
+
+                    old_palette.resize((index + count).max(old_palette.len()), [0; 3]);
+                    for color in &mut old_palette[index..index + count] {
+                        let rgb = chunk.take(3)?;
+                        *color = [rgb[0], rgb[1], rgb[2]];
+                    }
+                    index += count;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if palette.is_empty() {
+        palette = old_palette;
+    }
+
+    Ok(Frame {
+        layers,
+        cels,
+        palette,
+    })
+}
+
+/// Reads a cel chunk, or `None` for linked and tilemap cels.
+fn read_cel(chunk: &mut Reader, bytes_per_pixel: usize) -> Result<Option<Cel>, InvalidProject> {
+    let layer = usize::from(chunk.u16()?);
+    let x = chunk.i16()?;
+    let y = chunk.i16()?;
+    chunk.skip(1)?;
+    let cel_type = chunk.u16()?;
+    let z_index = chunk.i16()?;
+    chunk.skip(5)?;
+
+    if cel_type != CEL_RAW && cel_type != CEL_COMPRESSED {
+        return Ok(None);
+    }
+
+    let width = u32::from(chunk.u16()?);
+    let height = u32::from(chunk.u16()?);
+
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(InvalidProject);
+    }
+
+    let pixels = if cel_type == CEL_COMPRESSED {
+        // Anything past the pixels of the cel is never read.
+        let size = (width * height) as usize * bytes_per_pixel;
+        let mut pixels = vec![];
+        ZlibDecoder::new(chunk.0)
+            .take(size as u64)
+            .read_to_end(&mut pixels)
+            .map_err(|_| InvalidProject)?;
+        pixels
+    } else {
+        chunk.0.to_vec()
+    };
+
+    Ok(Some(Cel {
+        layer,
+        z_index,
+        x,
+        y,
+        width,
+        height,
+        pixels,
+    }))
+}
+
+pub fn is_piskel(ext: &str, bytes: &[u8]) -> bool {
+    ext.eq_ignore_ascii_case("piskel") || bytes.trim_ascii_start().starts_with(b"{")
+}
+
+/// Reads the first frame of a `.piskel` file, compositing its visible layers
+/// bottom to top. Piskel files carry no palette, so the colours in use stand
+/// in for it.
+pub fn decode_piskel(bytes: &[u8]) -> Result<Project, InvalidProject> {
+    #[derive(Deserialize)]
+    struct File {
+        piskel: Piskel,
+    }
+
+    #[derive(Deserialize)]
+    struct Piskel {
+        width: u32,
+        height: u32,
+        layers: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Layer {
+        #[serde(default = "full_opacity")]
+        opacity: f64,
+        #[serde(default)]
+        hidden: bool,
+        #[serde(default)]
+        chunks: Vec<Chunk>,
+        #[serde(rename = "base64PNG")]
+        base64_png: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Chunk {
+        layout: Vec<Vec<u32>>,
+        #[serde(rename = "base64PNG")]
+        base64_png: String,
+    }
+
+    fn full_opacity() -> f64 {
+        1.0
+    }
+
+    let file = serde_json::from_slice::<File>(bytes).map_err(|_| InvalidProject)?;
+    let (width, height) = (file.piskel.width, file.piskel.height);
+
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(InvalidProject);
+    }
+
+    let mut image = RgbaImage::new(width, height);
+
+    for layer in &file.piskel.layers {
+        let layer = serde_json::from_str::<Layer>(layer).map_err(|_| InvalidProject)?;
+
+        if layer.hidden || layer.opacity <= 0.0 {
+            continue;
+        }
+
+        // Frames are laid out in a grid, `layout[column][row]` holding the
+        // index of the frame in each cell. Older files have a single strip.
+        let (png, column, row) = if let Some(chunk) = layer.chunks.iter().find_map(|chunk| {
+            chunk.layout.iter().enumerate().find_map(|(column, rows)| {
+                let row = rows.iter().position(|frame| *frame == 0)?;
+                Some((&chunk.base64_png, column, row))
+            })
+        }) {
+            chunk
+        } else if let Some(png) = &layer.base64_png {
+            (png, 0, 0)
+        } else {
+            continue;
+        };
+
+        let png = png.split_once(',').map_or(png.as_str(), |(_, data)| data);
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(png)
+            .map_err(|_| InvalidProject)?;
+        let sheet = image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .map_err(|_| InvalidProject)?
+            .to_rgba8();
+
+        let offset = |cell: usize, side: u32| {
+            u32::try_from(cell)
+                .ok()
+                .and_then(|cell| cell.checked_mul(side))
+                .ok_or(InvalidProject)
+        };
+        let (x, y) = (offset(column, width)?, offset(row, height)?);
+        if x.checked_add(width)
+            .is_none_or(|right| right > sheet.width())
+            || y.checked_add(height)
+                .is_none_or(|bottom| bottom > sheet.height())
+        {
+            return Err(InvalidProject);
+        }
+
+        let frame = imageops::crop_imm(&sheet, x, y, width, height).to_image();
+
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            if pixel.0[3] != 0 {
+                image.put_pixel(x, y, *pixel);
+            }
+        }
+    }
+
+    let palette = image
+        .pixels()
+        .filter(|pixel| pixel.0[3] != 0)
+        .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]])
+        .unique()
+        .collect();
+
+    Ok(Project { image, palette })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], InvalidProject> {
+        if self.0.len() < n {
+            return Err(InvalidProject);
+        }
+
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), InvalidProject> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidProject> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, InvalidProject> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16, InvalidProject> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, InvalidProject> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
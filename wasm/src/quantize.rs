@@ -0,0 +1,208 @@
+use crate::{Dithering, QuantizationReport, color::Lab, palette};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// Error diffusion kernels as `(dx, dy, weight)`.
+const FLOYD_STEINBERG: &[(isize, isize, f64)] = &[
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+const ATKINSON: &[(isize, isize, f64)] = &[
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Amplitude of the ordered dithering offsets, in 8-bit channel units.
+const BAYER_SPREAD: f64 = 32.0;
+
+/// Maps every pixel to a palette code, optionally dithering, and reports how
+/// far the result strays from the source colours.
+pub fn quantize(image: &RgbImage, dithering: Dithering) -> (Vec<Vec<u8>>, QuantizationReport) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+
+    let source = image.pixels().map(|pixel| pixel.0).collect::<Vec<_>>();
+    let mut buffer = source
+        .iter()
+        .map(|pixel| pixel.map(f64::from))
+        .collect::<Vec<_>>();
+
+    let mut nearest = HashMap::new();
+    let mut data = vec![vec![0; width]; height];
+    let mut report = QuantizationReport {
+        changed_pixels: 0,
+        max_error: 0.0,
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut value = buffer[y * width + x];
+
+            if let Dithering::Bayer = dithering {
+                let offset = (f64::from(BAYER[y % 8][x % 8]) + 0.5) / 64.0 - 0.5;
+                value = value.map(|channel| channel + offset * BAYER_SPREAD);
+            }
+
+            let value = value.map(|channel| channel.clamp(0.0, 255.0));
+
+            let (code, _) = *nearest
+                .entry(value.map(to_channel))
+                .or_insert_with_key(|rgb| palette::nearest(*rgb));
+
+            let source = source[y * width + x];
+            let target = palette::COLORS.get(&code).unwrap().0;
+
+            if source != target {
+                report.changed_pixels += 1;
+                report.max_error = report
+                    .max_error
+                    .max(Lab::from_rgb(source).delta_e(Lab::from_rgb(target)));
+            }
+
+            data[y][x] = code;
+
+            let kernel = match dithering {
+                Dithering::FloydSteinberg => FLOYD_STEINBERG,
+                Dithering::Atkinson => ATKINSON,
+                Dithering::None | Dithering::Bayer => continue,
+            };
+
+            let error = [0, 1, 2].map(|i| value[i] - f64::from(target[i]));
+
+            for (dx, dy, weight) in kernel {
+                let (Some(nx), Some(ny)) = (x.checked_add_signed(*dx), y.checked_add_signed(*dy))
+                else {
+                    continue;
+                };
+
+                if nx < width && ny < height {
+                    let neighbour = &mut buffer[ny * width + nx];
+                    for i in 0..3 {
+                        neighbour[i] += error[i] * weight;
+                    }
+                }
+            }
+        }
+    }
+
+    (data, report)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_channel(value: f64) -> u8 {
+    value.round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn color(code: u8) -> Rgb<u8> {
+        *palette::COLORS.get(&code).unwrap()
+    }
+
+    #[test]
+    fn palette_colours_are_kept() {
+        let image = RgbImage::from_fn(8, 8, |x, y| color(u8::try_from(y * 8 + x).unwrap()));
+
+        let (data, report) = quantize(&image, Dithering::None);
+
+        for (y, row) in (0..).zip(&data) {
+            for (x, code) in (0..).zip(row) {
+                assert_eq!(*code, y * 8 + x);
+            }
+        }
+        assert_eq!(report.changed_pixels, 0);
+        assert!(report.max_error == 0.0);
+    }
+
+    #[test]
+    fn other_colours_map_to_the_nearest() {
+        let [r, g, b] = color(20).0;
+        let off = [r.saturating_add(3), g, b.saturating_sub(2)];
+        assert!(!palette::CODES.contains_key(&off));
+
+        let mut image = RgbImage::from_pixel(4, 4, color(20));
+        image.put_pixel(1, 2, Rgb(off));
+        image.put_pixel(3, 0, Rgb(off));
+
+        let (data, report) = quantize(&image, Dithering::None);
+
+        let (nearest, distance) = palette::nearest(off);
+        assert_eq!(nearest, 20);
+        assert_eq!(data[2][1], nearest);
+        assert_eq!(data[0][3], nearest);
+        assert_eq!(report.changed_pixels, 2);
+        assert!((report.max_error - distance).abs() < 1e-9);
+    }
+
+    /// Grey ramp from black to white along x.
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(64, 8, |x, _| {
+            let level = u8::try_from(x * 255 / 63).unwrap();
+            Rgb([level; 3])
+        })
+    }
+
+    /// Total difference between the mean colour of each 8×8 block of the
+    /// source and of its quantized codes, which dithering should reduce.
+    fn block_error(image: &RgbImage, data: &[Vec<u8>]) -> f64 {
+        let mut error = 0.0;
+
+        for block in 0..image.width() / 8 {
+            let mut source = [0.0; 3];
+            let mut target = [0.0; 3];
+            for (x, y) in (block * 8..block * 8 + 8).flat_map(|x| (0..8).map(move |y| (x, y))) {
+                let pixel = image.get_pixel(x, y).0;
+                let code = data[y as usize][x as usize];
+                for i in 0..3 {
+                    source[i] += f64::from(pixel[i]);
+                    target[i] += f64::from(color(code).0[i]);
+                }
+            }
+            error += (0..3)
+                .map(|i| (source[i] - target[i]).abs() / 64.0)
+                .sum::<f64>();
+        }
+
+        error
+    }
+
+    #[test]
+    fn dithering_is_deterministic_and_follows_the_gradient() {
+        let image = gradient();
+        let (plain, _) = quantize(&image, Dithering::None);
+
+        for dithering in [
+            Dithering::FloydSteinberg,
+            Dithering::Atkinson,
+            Dithering::Bayer,
+        ] {
+            let (data, report) = quantize(&image, dithering);
+            let (again, _) = quantize(&image, dithering);
+            assert!(data == again);
+
+            assert!(data != plain);
+            assert!(block_error(&image, &data) < block_error(&image, &plain));
+            assert!(report.changed_pixels > 0);
+        }
+    }
+}
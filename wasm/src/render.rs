@@ -1,9 +1,8 @@
+use crate::palette::COLORS;
 use image::{RgbImage, codecs::png::PngEncoder};
 use itertools::Itertools;
 use std::iter;
 
-static COLORS: phf::Map<u8, image::Rgb<u8>> = rust_colors::colors!();
-
 pub fn render(data: &[Vec<u8>], upscale: bool) -> Vec<u8> {
     let scale = if upscale { 10 } else { 1 };
     let pixels = data
@@ -30,6 +29,26 @@ pub fn render(data: &[Vec<u8>], upscale: bool) -> Vec<u8> {
     bytes
 }
 
+/// Renders text onto a background with a margin of one font pixel, for
+/// captions and watermarks.
+pub fn render_text(text: &str, code: u8, background: u8, scale: u32) -> Vec<u8> {
+    let (width, height) = pixel_font::measure(text, scale);
+    let mut image = RgbImage::from_pixel(
+        width + 2 * scale,
+        height + 2 * scale,
+        *COLORS.get(&background).unwrap(),
+    );
+
+    pixel_font::draw_text(&mut image, scale, scale, text, code, scale);
+
+    let mut bytes = vec![];
+    image
+        .write_with_encoder(PngEncoder::new(&mut bytes))
+        .unwrap();
+
+    bytes
+}
+
 trait IteratorExt<T> {
     fn repeat_n(self, n: usize) -> impl Iterator<Item = T>;
 }
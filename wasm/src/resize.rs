@@ -0,0 +1,220 @@
+use crate::Resize;
+use image::{Rgb, RgbImage, imageops};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+const SIZE: u32 = 64;
+
+/// Brings an image of any size to 64×64 according to `mode`. `Resize::None`
+/// only accepts 64×64 images and their exact integer upscales.
+pub fn resize(image: &RgbImage, mode: Resize, background: Rgb<u8>) -> Option<RgbImage> {
+    let (width, height) = image.dimensions();
+
+    Some(match mode {
+        Resize::None => return collapse_upscale(image),
+        Resize::Crop => {
+            let side = width.min(height);
+            let cropped =
+                imageops::crop_imm(image, (width - side) / 2, (height - side) / 2, side, side)
+                    .to_image();
+            downscale(&cropped, SIZE, SIZE)
+        }
+        Resize::Fit => downscale(image, SIZE, SIZE),
+        Resize::Pad => {
+            let side = u64::from(width.max(height));
+            let scale = |length: u32| {
+                let scaled = (u64::from(length) * u64::from(SIZE)).div_ceil(side);
+                u32::try_from(scaled).unwrap().min(SIZE)
+            };
+            let (scaled_width, scaled_height) = (scale(width), scale(height));
+            let scaled = downscale(image, scaled_width, scaled_height);
+
+            let mut padded = RgbImage::from_pixel(SIZE, SIZE, background);
+            imageops::replace(
+                &mut padded,
+                &scaled,
+                i64::from((SIZE - scaled_width) / 2),
+                i64::from((SIZE - scaled_height) / 2),
+            );
+            padded
+        }
+    })
+}
+
+/// Recovers the original 64×64 image from an exact integer upscale, such as
+/// our own 640×640 renders. Fails unless every block is a single colour.
+fn collapse_upscale(image: &RgbImage) -> Option<RgbImage> {
+    let (width, height) = image.dimensions();
+
+    if width != height || width % SIZE != 0 {
+        return None;
+    }
+
+    let scale = width / SIZE;
+
+    let collapsed = RgbImage::from_fn(SIZE, SIZE, |x, y| *image.get_pixel(x * scale, y * scale));
+
+    let is_exact = image
+        .enumerate_pixels()
+        .all(|(x, y, pixel)| collapsed.get_pixel(x / scale, y / scale) == pixel);
+
+    is_exact.then_some(collapsed)
+}
+
+/// Picks a pixel-art-aware downscaler: images that are integer upscales keep
+/// their hard edges by taking the dominant colour of each block, anything
+/// else is area averaged.
+fn downscale(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    if image.dimensions() == (width, height) {
+        return image.clone();
+    }
+
+    mode_of_blocks(image, width, height).unwrap_or_else(|| area_average(image, width, height))
+}
+
+/// Collapses each block to its most frequent colour, provided the image
+/// splits evenly into blocks and that colour covers most of every block.
+fn mode_of_blocks(image: &RgbImage, width: u32, height: u32) -> Option<RgbImage> {
+    let (source_width, source_height) = image.dimensions();
+
+    if source_width % width != 0 || source_height % height != 0 {
+        return None;
+    }
+
+    let (block_width, block_height) = (source_width / width, source_height / height);
+    let block_area = block_width * block_height;
+
+    let mut output = RgbImage::new(width, height);
+
+    for (x, y) in (0..width).cartesian_product(0..height) {
+        let mut counts = HashMap::<Rgb<u8>, u32>::new();
+
+        for (dx, dy) in (0..block_width).cartesian_product(0..block_height) {
+            *counts
+                .entry(*image.get_pixel(x * block_width + dx, y * block_height + dy))
+                .or_default() += 1;
+        }
+
+        let (color, count) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+
+        if count * 2 <= block_area {
+            return None;
+        }
+
+        output.put_pixel(x, y, color);
+    }
+
+    Some(output)
+}
+
+/// Averages the source pixels covered by each output pixel, weighted by the
+/// covered area.
+fn area_average(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let (source_width, source_height) = image.dimensions();
+
+    let scale_x = f64::from(source_width) / f64::from(width);
+    let scale_y = f64::from(source_height) / f64::from(height);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (x0, x1) = (f64::from(x) * scale_x, f64::from(x + 1) * scale_x);
+        let (y0, y1) = (f64::from(y) * scale_y, f64::from(y + 1) * scale_y);
+
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+
+        for sy in span(y0, y1) {
+            let weight_y = (y1.min(f64::from(sy + 1)) - y0.max(f64::from(sy))).max(0.0);
+
+            for sx in span(x0, x1) {
+                let weight_x = (x1.min(f64::from(sx + 1)) - x0.max(f64::from(sx))).max(0.0);
+                let weight = weight_x * weight_y;
+
+                let pixel = image.get_pixel(sx.min(source_width - 1), sy.min(source_height - 1));
+                for (acc, channel) in sum.iter_mut().zip(pixel.0) {
+                    *acc += f64::from(channel) * weight;
+                }
+                total += weight;
+            }
+        }
+
+        Rgb(sum.map(|acc| to_channel(acc / total)))
+    })
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn span(from: f64, to: f64) -> std::ops::Range<u32> {
+    (from.floor() as u32)..(to.ceil() as u32)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_channel(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    /// A 64×64 image with a different colour for every pixel.
+    fn pattern() -> RgbImage {
+        RgbImage::from_fn(SIZE, SIZE, |x, y| {
+            Rgb([
+                u8::try_from(x * 4).unwrap(),
+                u8::try_from(y * 4).unwrap(),
+                0,
+            ])
+        })
+    }
+
+    #[test]
+    fn crop_keeps_the_centre() {
+        let mut image = RgbImage::from_pixel(SIZE * 3, SIZE, RED);
+        imageops::replace(&mut image, &pattern(), i64::from(SIZE), 0);
+
+        assert!(resize(&image, Resize::Crop, BLUE).unwrap() == pattern());
+    }
+
+    #[test]
+    fn fit_stretches_with_hard_edges() {
+        let pattern = pattern();
+        let stretched = RgbImage::from_fn(SIZE * 2, SIZE, |x, y| *pattern.get_pixel(x / 2, y));
+
+        assert!(resize(&stretched, Resize::Fit, BLUE).unwrap() == pattern);
+    }
+
+    #[test]
+    fn pad_centres_and_fills_the_margins() {
+        let image = RgbImage::from_pixel(SIZE * 2, SIZE, RED);
+
+        let padded = resize(&image, Resize::Pad, BLUE).unwrap();
+
+        for (_, y, pixel) in padded.enumerate_pixels() {
+            let expected = if (16..48).contains(&y) { RED } else { BLUE };
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn mixed_blocks_are_averaged() {
+        let checkers = RgbImage::from_fn(SIZE * 2, SIZE * 2, |x, y| {
+            if (x + y) % 2 == 0 { RED } else { BLUE }
+        });
+
+        let averaged = resize(&checkers, Resize::Fit, BLUE).unwrap();
+
+        assert!(averaged.pixels().all(|pixel| *pixel == Rgb([128, 0, 128])));
+    }
+
+    #[test]
+    fn odd_sizes_are_averaged() {
+        let image = RgbImage::from_pixel(100, 70, RED);
+
+        let fitted = resize(&image, Resize::Fit, BLUE).unwrap();
+
+        assert_eq!(fitted.dimensions(), (SIZE, SIZE));
+        assert!(fitted.pixels().all(|pixel| *pixel == RED));
+    }
+}